@@ -0,0 +1,324 @@
+use std::collections::BTreeMap;
+
+use bevy::color::{Color, Srgba};
+use serde::{Deserialize, Serialize};
+
+/// The named colors of CSS, with their colors in `0xRRGGBB` format.
+const CSS_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xF0F8FF),
+    ("antiquewhite", 0xFAEBD7),
+    ("aqua", 0x00FFFF),
+    ("aquamarine", 0x7FFFD4),
+    ("azure", 0xF0FFFF),
+    ("beige", 0xF5F5DC),
+    ("bisque", 0xFFE4C4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xFFEBCD),
+    ("blue", 0x0000FF),
+    ("blueviolet", 0x8A2BE2),
+    ("brown", 0xA52A2A),
+    ("burlywood", 0xDEB887),
+    ("cadetblue", 0x5F9EA0),
+    ("chartreuse", 0x7FFF00),
+    ("chocolate", 0xD2691E),
+    ("coral", 0xFF7F50),
+    ("cornflowerblue", 0x6495ED),
+    ("cornsilk", 0xFFF8DC),
+    ("crimson", 0xDC143C),
+    ("cyan", 0x00FFFF),
+    ("darkblue", 0x00008B),
+    ("darkcyan", 0x008B8B),
+    ("darkgoldenrod", 0xB8860B),
+    ("darkgray", 0xA9A9A9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xA9A9A9),
+    ("darkkhaki", 0xBDB76B),
+    ("darkmagenta", 0x8B008B),
+    ("darkolivegreen", 0x556B2F),
+    ("darkorange", 0xFF8C00),
+    ("darkorchid", 0x9932CC),
+    ("darkred", 0x8B0000),
+    ("darksalmon", 0xE9967A),
+    ("darkseagreen", 0x8FBC8F),
+    ("darkslateblue", 0x483D8B),
+    ("darkslategray", 0x2F4F4F),
+    ("darkslategrey", 0x2F4F4F),
+    ("darkturquoise", 0x00CED1),
+    ("darkviolet", 0x9400D3),
+    ("deeppink", 0xFF1493),
+    ("deepskyblue", 0x00BFFF),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1E90FF),
+    ("firebrick", 0xB22222),
+    ("floralwhite", 0xFFFAF0),
+    ("forestgreen", 0x228B22),
+    ("fuchsia", 0xFF00FF),
+    ("gainsboro", 0xDCDCDC),
+    ("ghostwhite", 0xF8F8FF),
+    ("gold", 0xFFD700),
+    ("goldenrod", 0xDAA520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xADFF2F),
+    ("grey", 0x808080),
+    ("honeydew", 0xF0FFF0),
+    ("hotpink", 0xFF69B4),
+    ("indianred", 0xCD5C5C),
+    ("indigo", 0x4B0082),
+    ("ivory", 0xFFFFF0),
+    ("khaki", 0xF0E68C),
+    ("lavender", 0xE6E6FA),
+    ("lavenderblush", 0xFFF0F5),
+    ("lawngreen", 0x7CFC00),
+    ("lemonchiffon", 0xFFFACD),
+    ("lightblue", 0xADD8E6),
+    ("lightcoral", 0xF08080),
+    ("lightcyan", 0xE0FFFF),
+    ("lightgoldenrodyellow", 0xFAFAD2),
+    ("lightgray", 0xD3D3D3),
+    ("lightgreen", 0x90EE90),
+    ("lightgrey", 0xD3D3D3),
+    ("lightpink", 0xFFB6C1),
+    ("lightsalmon", 0xFFA07A),
+    ("lightseagreen", 0x20B2AA),
+    ("lightskyblue", 0x87CEFA),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xB0C4DE),
+    ("lightyellow", 0xFFFFE0),
+    ("lime", 0x00FF00),
+    ("limegreen", 0x32CD32),
+    ("linen", 0xFAF0E6),
+    ("magenta", 0xFF00FF),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66CDAA),
+    ("mediumblue", 0x0000CD),
+    ("mediumorchid", 0xBA55D3),
+    ("mediumpurple", 0x9370DB),
+    ("mediumseagreen", 0x3CB371),
+    ("mediumslateblue", 0x7B68EE),
+    ("mediumspringgreen", 0x00FA9A),
+    ("mediumturquoise", 0x48D1CC),
+    ("mediumvioletred", 0xC71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xF5FFFA),
+    ("mistyrose", 0xFFE4E1),
+    ("moccasin", 0xFFE4B5),
+    ("navajowhite", 0xFFDEAD),
+    ("navy", 0x000080),
+    ("oldlace", 0xFDF5E6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6B8E23),
+    ("orange", 0xFFA500),
+    ("orangered", 0xFF4500),
+    ("orchid", 0xDA70D6),
+    ("palegoldenrod", 0xEEE8AA),
+    ("palegreen", 0x98FB98),
+    ("paleturquoise", 0xAFEEEE),
+    ("palevioletred", 0xDB7093),
+    ("papayawhip", 0xFFEFD5),
+    ("peachpuff", 0xFFDAB9),
+    ("peru", 0xCD853F),
+    ("pink", 0xFFC0CB),
+    ("plum", 0xDDA0DD),
+    ("powderblue", 0xB0E0E6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xFF0000),
+    ("rosybrown", 0xBC8F8F),
+    ("royalblue", 0x4169E1),
+    ("saddlebrown", 0x8B4513),
+    ("salmon", 0xFA8072),
+    ("sandybrown", 0xF4A460),
+    ("seagreen", 0x2E8B57),
+    ("seashell", 0xFFF5EE),
+    ("sienna", 0xA0522D),
+    ("silver", 0xC0C0C0),
+    ("skyblue", 0x87CEEB),
+    ("slateblue", 0x6A5ACD),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xFFFAFA),
+    ("springgreen", 0x00FF7F),
+    ("steelblue", 0x4682B4),
+    ("tan", 0xD2B48C),
+    ("teal", 0x008080),
+    ("thistle", 0xD8BFD8),
+    ("tomato", 0xFF6347),
+    ("turquoise", 0x40E0D0),
+    ("violet", 0xEE82EE),
+    ("wheat", 0xF5DEB3),
+    ("white", 0xFFFFFF),
+    ("whitesmoke", 0xF5F5F5),
+    ("yellow", 0xFFFF00),
+    ("yellowgreen", 0x9ACD32),
+];
+
+/// Returns the CSS color with the given name, the name is case insensitive.
+/// `transparent` is also accepted, as it is in CSS.
+pub fn css_color(name: &str) -> Option<Color>
+{
+    let name = name.to_ascii_lowercase();
+
+    if name == "transparent" {
+        return Some(Color::NONE);
+    }
+
+    CSS_COLORS
+        .iter()
+        .find(|(css_name, _)| *css_name == name)
+        .map(|(_, rgb)| {
+            let [_, red, green, blue] = rgb.to_be_bytes();
+
+            Color::srgb_u8(red, green, blue)
+        })
+}
+
+/// Parses a hex color, ie. `#ff8800`.
+/// The `#rgb`, `#rgba`, `#rrggbb` and `#rrggbbaa` formats are accepted, this function also accepts them without the leading `#`.
+/// [`Palette::parse`] and the `color()` lua function only treat the text as a hex color if it starts with `#`, so that it can't be mistaken for a color name.
+pub fn hex_color(hex: &str) -> Option<Color>
+{
+    Srgba::hex(hex).ok().map(Color::from)
+}
+
+/// Creates a color from its hue (in degrees), saturation, value and alpha.
+/// The saturation, value and alpha range from 0.0 to 1.0.
+pub fn hsv_color(hue: f32, saturation: f32, value: f32, alpha: f32) -> Color
+{
+    Color::hsva(hue.rem_euclid(360.), saturation, value, alpha)
+}
+
+/// Returns the sRGB components of the color in (red, green, blue, alpha) format.
+/// This is the format the scripts set the colors in, unlike [`crate::color_into_vec4`] which returns the linear components.
+pub fn color_into_srgba(color: Color) -> [f32; 4]
+{
+    let color = color.to_srgba();
+
+    [color.red, color.green, color.blue, color.alpha]
+}
+
+/// The named colors defined by the scripts of the project.
+/// These colors can be used by their names everywhere a CSS color name can be used, and they take precedence over the CSS colors.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Palette
+{
+    colors: BTreeMap<String, Color>,
+}
+
+impl Palette
+{
+    /// Returns the colors by their names.
+    pub fn colors(&self) -> &BTreeMap<String, Color>
+    {
+        &self.colors
+    }
+
+    /// Adds a color to the palette, this overwrites the color with the same name.
+    /// The names are case insensitive like the CSS color names.
+    pub fn insert(&mut self, name: &str, color: Color)
+    {
+        self.colors.insert(name.to_ascii_lowercase(), color);
+    }
+
+    /// Removes the color with the given name from the palette.
+    pub fn remove(&mut self, name: &str) -> Option<Color>
+    {
+        self.colors.remove(&name.to_ascii_lowercase())
+    }
+
+    /// Parses a color from a string, which is either a name from the palette, a CSS color name or a hex color starting with `#`.
+    pub fn parse(&self, text: &str) -> Option<Color>
+    {
+        let text = text.trim();
+
+        if text.starts_with('#') {
+            return hex_color(text);
+        }
+
+        self.colors
+            .get(&text.to_ascii_lowercase())
+            .copied()
+            .or_else(|| css_color(text))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn parses_hex_colors()
+    {
+        let orange = Color::srgb_u8(0xff, 0x88, 0x00);
+
+        assert_eq!(hex_color("#ff8800"), Some(orange));
+        assert_eq!(hex_color("#f80"), Some(orange));
+        assert_eq!(hex_color("#FF8800"), Some(orange));
+        assert_eq!(hex_color("ff8800"), Some(orange));
+
+        assert_eq!(
+            hex_color("#ff880080"),
+            Some(Color::srgba_u8(0xff, 0x88, 0x00, 0x80))
+        );
+        assert_eq!(
+            hex_color("#f808"),
+            Some(Color::srgba_u8(0xff, 0x88, 0x00, 0x88))
+        );
+
+        assert_eq!(hex_color("#ff880"), None);
+        assert_eq!(hex_color("#gg8800"), None);
+        assert_eq!(hex_color("#"), None);
+    }
+
+    #[test]
+    fn parses_css_colors()
+    {
+        assert_eq!(css_color("red"), Some(Color::srgb_u8(0xff, 0x00, 0x00)));
+        assert_eq!(css_color("CornflowerBlue"), Some(Color::srgb_u8(0x64, 0x95, 0xed)));
+        assert_eq!(css_color("transparent"), Some(Color::NONE));
+        assert_eq!(css_color("notacolor"), None);
+    }
+
+    #[test]
+    fn wraps_the_hue()
+    {
+        let green = color_into_srgba(hsv_color(120., 1., 1., 1.));
+
+        for hue in [480., -240., 840.] {
+            let wrapped = color_into_srgba(hsv_color(hue, 1., 1., 1.));
+
+            for (wrapped, green) in wrapped.iter().zip(green) {
+                assert!((wrapped - green).abs() < 0.0001, "{hue} isn't wrapped around.");
+            }
+        }
+
+        assert_eq!(color_into_srgba(hsv_color(0., 1., 1., 0.5))[3], 0.5);
+    }
+
+    #[test]
+    fn parses_palette_colors()
+    {
+        let mut palette = Palette::default();
+
+        palette.insert("Sky", Color::srgb_u8(0x87, 0xce, 0xeb));
+        palette.insert("red", Color::srgb_u8(0xaa, 0x00, 0x00));
+
+        // The names are case insensitive, and the palette takes precedence over the CSS colors.
+        assert_eq!(palette.parse("sky"), Some(Color::srgb_u8(0x87, 0xce, 0xeb)));
+        assert_eq!(palette.parse(" SKY "), Some(Color::srgb_u8(0x87, 0xce, 0xeb)));
+        assert_eq!(palette.parse("Red"), Some(Color::srgb_u8(0xaa, 0x00, 0x00)));
+        assert_eq!(palette.parse("blue"), css_color("blue"));
+
+        // Only the texts starting with a `#` are hex colors, so that they can't be mistaken for names.
+        assert_eq!(palette.parse("#ff8800"), hex_color("#ff8800"));
+        assert_eq!(palette.parse("ff8800"), None);
+        assert_eq!(palette.parse("#sky"), None);
+
+        assert_eq!(palette.remove("SKY"), Some(Color::srgb_u8(0x87, 0xce, 0xeb)));
+        assert_eq!(palette.parse("sky"), None);
+    }
+}
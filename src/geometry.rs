@@ -0,0 +1,1287 @@
+use std::{
+    collections::{HashMap, HashSet},
+    f32::consts::PI,
+};
+
+use bevy::math::{Vec2, Vec3, Vec4};
+use geo::{Contains, Coord, Intersects, LineString, Point, Polygon};
+
+use crate::{color_into_vec4, Line, LineCap, LineJoin, LineStrip};
+
+/// The maximum distance between the tip of a miter join and the joint, relative to the line's half width.
+/// Sharper corners fall back to a bevel join, so that they do not produce extremely long spikes.
+pub const MITER_LIMIT: f32 = 4.;
+
+/// The maximum distance allowed between a round cap or join and its tessellated outline.
+const ROUND_TOLERANCE: f32 = 0.1;
+
+/// Points closer to each other than this are treated as the same point.
+const POINT_EPSILON: f32 = 0.0001;
+
+/// The maximum distance allowed between a curve and the straight lines it is flattened into.
+/// The canvas can't be zoomed, so this is measured in logical pixels, and it has to be lowered if zooming is ever added.
+const CURVE_TOLERANCE: f32 = 0.05;
+
+/// The maximum number of times a curve is split in half while flattening it, which limits a curve to 1024 points.
+const MAX_CURVE_SUBDIVISIONS: u32 = 10;
+
+/// The size of the grid the points of the lines are snapped to while looking for closed regions.
+/// Points closer to each other than this are usually treated as the same point.
+const SNAP_DISTANCE: f32 = 0.001;
+
+/// A list of triangles, every three consecutive vertices make up a triangle.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TriangleBuffer
+{
+    /// The positions of the vertices.
+    pub positions: Vec<Vec3>,
+    /// The colors of the vertices.
+    pub colors: Vec<Vec4>,
+}
+
+impl TriangleBuffer
+{
+    fn push_triangle(&mut self, a: Vec2, b: Vec2, c: Vec2, color: Vec4)
+    {
+        self.positions
+            .extend([a.extend(0.), b.extend(0.), c.extend(0.)]);
+        self.colors.extend([color; 3]);
+    }
+
+    /// Pushes a quad, the corners must be in order around the quad.
+    fn push_quad(&mut self, a: Vec2, b: Vec2, c: Vec2, d: Vec2, color: Vec4)
+    {
+        self.push_triangle(a, b, c, color);
+        self.push_triangle(a, c, d, color);
+    }
+
+    /// Pushes a fan of triangles around `center`, starting at the `from` direction and rotating by `sweep` radians.
+    /// The radius is linearly interpolated from `start_radius` to `end_radius` along the arc.
+    fn push_arc(
+        &mut self,
+        center: Vec2,
+        from: Vec2,
+        sweep: f32,
+        start_radius: f32,
+        end_radius: f32,
+        color: Vec4,
+    )
+    {
+        let steps = arc_step_count(start_radius.max(end_radius), sweep.abs());
+
+        let mut previous = center + from * start_radius;
+
+        for step in 1..=steps {
+            let t = step as f32 / steps as f32;
+            let radius = start_radius + (end_radius - start_radius) * t;
+            let current = center + Vec2::from_angle(sweep * t).rotate(from) * radius;
+
+            self.push_triangle(center, previous, current, color);
+
+            previous = current;
+        }
+    }
+}
+
+/// Returns how many steps an arc with the given radius and angle (in radians) should be split up to, to stay within [`ROUND_TOLERANCE`].
+fn arc_step_count(radius: f32, angle: f32) -> u32
+{
+    if radius <= ROUND_TOLERANCE {
+        return 1;
+    }
+
+    let step_angle = 2. * (1. - ROUND_TOLERANCE / radius).acos();
+
+    ((angle / step_angle).ceil() as u32).clamp(1, 1024)
+}
+
+/// Returns the points of an elliptic arc, the point at angle `t` is `center + x_axis * cos(t) + y_axis * sin(t)`.
+/// The arc starts at `center + x_axis` (which is not included in the list) and goes on for `sweep` radians.
+/// A circular arc is described by two perpendicular axes of the same length.
+pub fn elliptic_arc_points(center: Vec2, x_axis: Vec2, y_axis: Vec2, sweep: f32) -> Vec<Vec2>
+{
+    let steps = arc_step_count(x_axis.length().max(y_axis.length()), sweep.abs());
+
+    (1..=steps)
+        .map(|step| {
+            let angle = sweep * step as f32 / steps as f32;
+
+            center + x_axis * angle.cos() + y_axis * angle.sin()
+        })
+        .collect()
+}
+
+/// A single straight part of a [`LineStrip`].
+struct Segment
+{
+    start: Vec2,
+    end: Vec2,
+    /// The unit vector pointing from `start` to `end`.
+    direction: Vec2,
+    /// The unit vector pointing to the left side of the segment.
+    normal: Vec2,
+    half_width: f32,
+    color: Vec4,
+}
+
+/// Turns a [`LineStrip`] into a list of triangles.
+/// Every segment is drawn with the width and color of the point it was drawn to, the ends and corners are shaped by the strip's [`LineCap`] and [`LineJoin`].
+/// If the strip's last point is its first point, the strip is closed and the ends are joined instead of capped.
+pub fn tessellate_line_strip(line: &LineStrip) -> TriangleBuffer
+{
+    let mut triangles = TriangleBuffer::default();
+
+    let segments: Vec<Segment> = line
+        .points
+        .windows(2)
+        .filter_map(|points| {
+            let (start, end) = (points[0].0.truncate(), points[1].0.truncate());
+
+            // Skip the segments which do not have a direction
+            let direction = (end - start).try_normalize()?;
+
+            Some(Segment {
+                start,
+                end,
+                direction,
+                normal: direction.perp(),
+                half_width: points[1].2.max(0.) / 2.,
+                color: color_into_vec4(points[1].1),
+            })
+        })
+        .collect();
+
+    let (Some(first), Some(last)) = (segments.first(), segments.last())
+    else {
+        return triangles;
+    };
+
+    for segment in &segments {
+        let offset = segment.normal * segment.half_width;
+
+        triangles.push_quad(
+            segment.start + offset,
+            segment.end + offset,
+            segment.end - offset,
+            segment.start - offset,
+            segment.color,
+        );
+    }
+
+    for segments in segments.windows(2) {
+        push_join(&mut triangles, line.join, &segments[0], &segments[1]);
+    }
+
+    let is_closed = segments.len() > 1 && first.start.distance(last.end) < POINT_EPSILON;
+
+    if is_closed {
+        push_join(&mut triangles, line.join, last, first);
+    }
+    else {
+        push_cap(&mut triangles, line.cap, first.start, -first.direction, first);
+        push_cap(&mut triangles, line.cap, last.end, last.direction, last);
+    }
+
+    triangles
+}
+
+/// Fills the gap on the outer side of the corner between two consecutive segments.
+fn push_join(triangles: &mut TriangleBuffer, join: LineJoin, incoming: &Segment, outgoing: &Segment)
+{
+    let cross = incoming.direction.perp_dot(outgoing.direction);
+
+    // If the line continues straight, the segments already meet.
+    if cross.abs() < POINT_EPSILON && incoming.direction.dot(outgoing.direction) > 0. {
+        return;
+    }
+
+    let pivot = outgoing.start;
+
+    // When turning left the gap opens up on the right side, and the other way around.
+    let side = if cross > 0. { -1. } else { 1. };
+
+    let from = incoming.normal * side;
+    let to = outgoing.normal * side;
+
+    let incoming_corner = pivot + from * incoming.half_width;
+    let outgoing_corner = pivot + to * outgoing.half_width;
+
+    match join {
+        LineJoin::Miter => {
+            // The tip is where the outer edges of the two segments intersect.
+            if cross.abs() >= POINT_EPSILON {
+                let distance =
+                    (outgoing_corner - incoming_corner).perp_dot(outgoing.direction) / cross;
+                let tip = incoming_corner + incoming.direction * distance;
+
+                if tip.distance(pivot)
+                    <= MITER_LIMIT * incoming.half_width.max(outgoing.half_width)
+                {
+                    triangles.push_quad(
+                        pivot,
+                        incoming_corner,
+                        tip,
+                        outgoing_corner,
+                        outgoing.color,
+                    );
+
+                    return;
+                }
+            }
+
+            triangles.push_triangle(pivot, incoming_corner, outgoing_corner, outgoing.color);
+        },
+        LineJoin::Round => {
+            // Reversing the direction has no clear turning side, so we always go around the same way.
+            let sweep = if cross.abs() < POINT_EPSILON {
+                PI
+            }
+            else {
+                incoming.direction.angle_to(outgoing.direction)
+            };
+
+            triangles.push_arc(
+                pivot,
+                from,
+                sweep,
+                incoming.half_width,
+                outgoing.half_width,
+                outgoing.color,
+            );
+        },
+        LineJoin::Bevel => {
+            triangles.push_triangle(pivot, incoming_corner, outgoing_corner, outgoing.color);
+        },
+    }
+}
+
+/// Draws the cap at one of the ends of a line, `direction` points away from the line.
+fn push_cap(
+    triangles: &mut TriangleBuffer,
+    cap: LineCap,
+    end: Vec2,
+    direction: Vec2,
+    segment: &Segment,
+)
+{
+    let normal = direction.perp();
+
+    match cap {
+        LineCap::Butt => (),
+        LineCap::Round => {
+            triangles.push_arc(
+                end,
+                -normal,
+                PI,
+                segment.half_width,
+                segment.half_width,
+                segment.color,
+            );
+        },
+        LineCap::Square => {
+            let offset = normal * segment.half_width;
+            let extension = direction * segment.half_width;
+
+            triangles.push_quad(
+                end + offset,
+                end + offset + extension,
+                end - offset + extension,
+                end - offset,
+                segment.color,
+            );
+        },
+    }
+}
+
+/// The vertices and the triangles of a triangulated polygon.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PolygonTriangulation
+{
+    /// The vertices of the polygon, the outline's vertices are followed by the holes' vertices.
+    pub positions: Vec<Vec2>,
+    /// The indices of the vertices, every three consecutive indices make up a triangle.
+    pub indices: Vec<u32>,
+}
+
+/// Turns a polygon into triangles with ear clipping, the polygon may be concave and may have holes.
+/// The holes are connected to the outline with bridges first, so that the polygon can be clipped as a single outline.
+/// The points of the outline and the holes can go around in either direction, and they do not have to be closed.
+pub fn triangulate_polygon(outline: &[Vec2], holes: &[Vec<Vec2>]) -> PolygonTriangulation
+{
+    let mut positions = vec![];
+
+    let outline = clean_ring(outline);
+
+    if outline.len() < 3 || ring_area(&outline).abs() < POINT_EPSILON {
+        return PolygonTriangulation::default();
+    }
+
+    let mut ring = push_ring(&mut positions, &outline, true);
+
+    let mut hole_rings: Vec<Vec<usize>> = holes
+        .iter()
+        .map(|hole| clean_ring(hole))
+        .filter(|hole| hole.len() >= 3 && ring_area(hole).abs() >= POINT_EPSILON)
+        .map(|hole| push_ring(&mut positions, &hole, false))
+        .collect();
+
+    // The holes are bridged from right to left, so that every bridge only has to avoid the holes which are already part of the outline.
+    hole_rings.sort_by(|a, b| {
+        let max_x = |hole: &[usize]| {
+            hole.iter()
+                .map(|idx| positions[*idx].x)
+                .fold(f32::MIN, f32::max)
+        };
+
+        max_x(b).total_cmp(&max_x(a))
+    });
+
+    for hole in &hole_rings {
+        bridge_hole(&positions, &mut ring, hole);
+    }
+
+    let indices = clip_ears(&positions, ring);
+
+    PolygonTriangulation { positions, indices }
+}
+
+/// Removes the repeated points of a ring, including the last point if it closes the ring.
+fn clean_ring(points: &[Vec2]) -> Vec<Vec2>
+{
+    let mut ring: Vec<Vec2> = vec![];
+
+    for point in points {
+        if ring
+            .last()
+            .is_none_or(|last| last.distance(*point) >= POINT_EPSILON)
+        {
+            ring.push(*point);
+        }
+    }
+
+    while ring.len() > 1 && ring[0].distance(*ring.last().unwrap()) < POINT_EPSILON {
+        ring.pop();
+    }
+
+    ring
+}
+
+/// Returns the signed area of a ring, which is positive if the ring goes around counter-clockwise.
+fn ring_area(ring: &[Vec2]) -> f32
+{
+    (0..ring.len())
+        .map(|idx| ring[idx].perp_dot(ring[(idx + 1) % ring.len()]))
+        .sum::<f32>()
+        / 2.
+}
+
+/// Adds the points of the ring to the positions, going around counter-clockwise if `counter_clockwise` is true and clockwise otherwise.
+/// Returns the indices of the ring's points.
+fn push_ring(positions: &mut Vec<Vec2>, ring: &[Vec2], counter_clockwise: bool) -> Vec<usize>
+{
+    let start = positions.len();
+
+    positions.extend_from_slice(ring);
+
+    if (ring_area(ring) > 0.) != counter_clockwise {
+        positions[start..].reverse();
+    }
+
+    (start..positions.len()).collect()
+}
+
+/// Returns whether the point of the ring at `idx` is reflex, ie. the ring turns right there.
+fn is_reflex(positions: &[Vec2], ring: &[usize], idx: usize) -> bool
+{
+    let previous = positions[ring[(idx + ring.len() - 1) % ring.len()]];
+    let current = positions[ring[idx]];
+    let next = positions[ring[(idx + 1) % ring.len()]];
+
+    (current - previous).perp_dot(next - current) <= 0.
+}
+
+/// Returns whether the point is inside of the counter-clockwise triangle, or on one of its edges.
+fn is_in_triangle(point: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool
+{
+    (b - a).perp_dot(point - a) >= 0.
+        && (c - b).perp_dot(point - b) >= 0.
+        && (a - c).perp_dot(point - c) >= 0.
+}
+
+/// Connects a clockwise hole to the counter-clockwise outline, by cutting the polygon from the hole's rightmost point to a point of the outline it can see.
+/// The outline walks around the hole and comes back on the same bridge, so the hole becomes part of the outline.
+fn bridge_hole(positions: &[Vec2], ring: &mut Vec<usize>, hole: &[usize])
+{
+    let (hole_start, hole_point) = hole
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| positions[**a].x.total_cmp(&positions[**b].x))
+        .map(|(idx, point)| (idx, positions[*point]))
+        .unwrap();
+
+    // Cast a ray from the hole's point to the right, and find the closest edge of the outline it hits.
+    let mut closest_hit: Option<(Vec2, usize)> = None;
+
+    for (idx, point) in ring.iter().enumerate() {
+        let next_idx = (idx + 1) % ring.len();
+        let (a, b) = (positions[*point], positions[ring[next_idx]]);
+
+        if (a.y > hole_point.y) == (b.y > hole_point.y) {
+            continue;
+        }
+
+        let hit_x = a.x + (hole_point.y - a.y) / (b.y - a.y) * (b.x - a.x);
+
+        if hit_x < hole_point.x
+            || closest_hit.is_some_and(|(closest, _)| closest.x <= hit_x)
+        {
+            continue;
+        }
+
+        // The endpoint of the edge which is further to the right is the candidate of the bridge.
+        let candidate = if a.x > b.x { idx } else { next_idx };
+
+        closest_hit = Some((Vec2::new(hit_x, hole_point.y), candidate));
+    }
+
+    let bridge_idx = match closest_hit {
+        Some((hit, candidate)) => {
+            let candidate_point = positions[ring[candidate]];
+
+            // Reflex points of the outline inside the triangle of the hit could block the view to the candidate.
+            // The one with the smallest angle to the ray can always be seen from the hole.
+            (0..ring.len())
+                .filter(|idx| {
+                    let point = positions[ring[*idx]];
+
+                    *idx != candidate
+                        && point != candidate_point
+                        && is_reflex(positions, ring, *idx)
+                        && (is_in_triangle(point, hole_point, hit, candidate_point)
+                            || is_in_triangle(point, hole_point, candidate_point, hit))
+                })
+                .min_by(|a, b| {
+                    let angle = |idx: &usize| {
+                        let direction = positions[ring[*idx]] - hole_point;
+
+                        (direction.y.abs().atan2(direction.x), direction.length())
+                    };
+
+                    angle(a)
+                        .partial_cmp(&angle(b))
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })
+                .unwrap_or(candidate)
+        },
+        // The hole is outside of the outline, so we connect it to the closest point.
+        None => {
+            (0..ring.len())
+                .min_by(|a, b| {
+                    positions[ring[*a]]
+                        .distance(hole_point)
+                        .total_cmp(&positions[ring[*b]].distance(hole_point))
+                })
+                .unwrap()
+        },
+    };
+
+    let hole_walk = hole[hole_start..]
+        .iter()
+        .chain(&hole[..=hole_start])
+        .copied()
+        .chain([ring[bridge_idx]]);
+
+    ring.splice(bridge_idx + 1..bridge_idx + 1, hole_walk.collect::<Vec<usize>>());
+}
+
+/// Clips the ears of the counter-clockwise ring one by one, until it is only a single triangle.
+/// Returns the indices of the clipped triangles.
+fn clip_ears(positions: &[Vec2], mut ring: Vec<usize>) -> Vec<u32>
+{
+    let mut indices = vec![];
+
+    // The number of points checked since the last one was clipped.
+    let mut checked_points = 0;
+    let mut idx = 0;
+
+    while ring.len() > 3 {
+        idx %= ring.len();
+
+        let previous = ring[(idx + ring.len() - 1) % ring.len()];
+        let current = ring[idx];
+        let next = ring[(idx + 1) % ring.len()];
+
+        let (a, b, c) = (positions[previous], positions[current], positions[next]);
+
+        let cross = (b - a).perp_dot(c - b);
+
+        // Points on a straight line do not make up a triangle, so they can be dropped.
+        if cross.abs() < POINT_EPSILON {
+            ring.remove(idx);
+            checked_points = 0;
+
+            continue;
+        }
+
+        let is_ear = cross > 0.
+            && !(0..ring.len()).any(|other_idx| {
+                let point = positions[ring[other_idx]];
+
+                // The bridges of the holes repeat points, those are not inside of the ear.
+                point != a
+                    && point != b
+                    && point != c
+                    && is_reflex(positions, &ring, other_idx)
+                    && is_in_triangle(point, a, b, c)
+            });
+
+        // If the polygon intersects itself there may be no ears left, so we clip the point anyway to make sure that we finish.
+        if is_ear || checked_points >= ring.len() {
+            indices.extend([previous as u32, current as u32, next as u32]);
+            ring.remove(idx);
+            checked_points = 0;
+        }
+        else {
+            idx += 1;
+            checked_points += 1;
+        }
+    }
+
+    if let [a, b, c] = ring[..] {
+        if (positions[b] - positions[a])
+            .perp_dot(positions[c] - positions[b])
+            .abs()
+            >= POINT_EPSILON
+        {
+            indices.extend([a as u32, b as u32, c as u32]);
+        }
+    }
+
+    indices
+}
+
+/// Returns the parameters of the points where the two segments meet, the point is at `from + (to - from) * t` on both segments.
+/// If the segments are on the same line, the ends of each segment which are on the other one are returned.
+pub fn segment_intersections(first: (Vec2, Vec2), second: (Vec2, Vec2)) -> Vec<(f32, f32)>
+{
+    let ((a, b), (c, d)) = (first, second);
+
+    let (r, s) = (b - a, d - c);
+
+    // Segments which are too far from each other can not meet.
+    if a.max(b).x + POINT_EPSILON < c.min(d).x
+        || c.max(d).x + POINT_EPSILON < a.min(b).x
+        || a.max(b).y + POINT_EPSILON < c.min(d).y
+        || c.max(d).y + POINT_EPSILON < a.min(b).y
+    {
+        return vec![];
+    }
+
+    let (r_length, s_length) = (r.length(), s.length());
+
+    if r_length < POINT_EPSILON || s_length < POINT_EPSILON {
+        return vec![];
+    }
+
+    let denominator = r.perp_dot(s);
+    let offset = c - a;
+
+    if denominator.abs() <= f32::EPSILON * r_length * s_length {
+        // Parallel segments only meet if they are on the same line.
+        if (offset.perp_dot(r) / r_length).abs() >= POINT_EPSILON {
+            return vec![];
+        }
+
+        let on_first = |point: Vec2| (point - a).dot(r) / (r_length * r_length);
+        let on_second = |point: Vec2| (point - c).dot(s) / (s_length * s_length);
+
+        let (first_tolerance, second_tolerance) =
+            (POINT_EPSILON / r_length, POINT_EPSILON / s_length);
+
+        let mut intersections = vec![];
+
+        for (u, point) in [(0., c), (1., d)] {
+            let t = on_first(point);
+
+            if (-first_tolerance..=1. + first_tolerance).contains(&t) {
+                intersections.push((t.clamp(0., 1.), u));
+            }
+        }
+
+        for (t, point) in [(0., a), (1., b)] {
+            let u = on_second(point);
+
+            if (-second_tolerance..=1. + second_tolerance).contains(&u) {
+                intersections.push((t, u.clamp(0., 1.)));
+            }
+        }
+
+        return intersections;
+    }
+
+    let t = offset.perp_dot(s) / denominator;
+    let u = offset.perp_dot(r) / denominator;
+
+    let (first_tolerance, second_tolerance) = (POINT_EPSILON / r_length, POINT_EPSILON / s_length);
+
+    if (-first_tolerance..=1. + first_tolerance).contains(&t)
+        && (-second_tolerance..=1. + second_tolerance).contains(&u)
+    {
+        vec![(t.clamp(0., 1.), u.clamp(0., 1.))]
+    }
+    else {
+        vec![]
+    }
+}
+
+/// Creates a [`geo`] polygon from a ring of points, so that points can be tested against it.
+fn geo_polygon(ring: &[Vec2]) -> Polygon<f64>
+{
+    Polygon::new(
+        LineString::new(
+            ring.iter()
+                .map(|point| {
+                    Coord {
+                        x: point.x as f64,
+                        y: point.y as f64,
+                    }
+                })
+                .collect(),
+        ),
+        vec![],
+    )
+}
+
+fn geo_point(point: Vec2) -> Point<f64>
+{
+    Point::new(point.x as f64, point.y as f64)
+}
+
+/// Finds the smallest closed region around the point, which is enclosed by the lines.
+/// The lines are split up where they meet, so the region can be bounded by any part of any of the lines.
+/// The point can also be on the border of the region, ie. at the corner of a shape that was just drawn.
+/// Returns the outline of the region and the outlines of the closed shapes inside of it, which are its holes.
+pub fn enclosing_region(lines: &[Line], point: Vec2) -> Option<(Vec<Vec2>, Vec<Vec<Vec2>>)>
+{
+    let segments: Vec<(Vec2, Vec2)> = lines
+        .iter()
+        .map(|line| (line.min.truncate(), line.max.truncate()))
+        .filter(|(start, end)| start.distance(*end) >= POINT_EPSILON)
+        .collect();
+
+    // Collect the points where the segments have to be split up.
+    let mut split_points: Vec<Vec<Vec2>> = segments
+        .iter()
+        .map(|(start, end)| vec![*start, *end])
+        .collect();
+
+    // Only the segments which overlap horizontally can meet, so the segments are swept from left to right,
+    // and every segment is only tested against the ones starting before it ends.
+    let left = |idx: &usize| segments[*idx].0.x.min(segments[*idx].1.x);
+    let right = |idx: &usize| segments[*idx].0.x.max(segments[*idx].1.x);
+
+    let mut sweep_order: Vec<usize> = (0..segments.len()).collect();
+
+    sweep_order.sort_by(|a, b| left(a).total_cmp(&left(b)));
+
+    for (order_idx, first_idx) in sweep_order.iter().enumerate() {
+        let sweep_end = right(first_idx) + POINT_EPSILON;
+
+        for second_idx in sweep_order[order_idx + 1..]
+            .iter()
+            .take_while(|second_idx| left(second_idx) <= sweep_end)
+        {
+            let (start, end) = segments[*first_idx];
+
+            for (t, _) in segment_intersections(segments[*first_idx], segments[*second_idx]) {
+                // The same point is added to both of the segments, so that they meet at exactly the same place.
+                let intersection = start.lerp(end, t);
+
+                split_points[*first_idx].push(intersection);
+                split_points[*second_idx].push(intersection);
+            }
+        }
+    }
+
+    // Snap the points to a grid, so that the points which are at the same place become the same vertex.
+    let mut vertices: Vec<Vec2> = vec![];
+    let mut vertex_indices: HashMap<(i64, i64), usize> = HashMap::new();
+    let mut edges: HashSet<(usize, usize)> = HashSet::new();
+
+    for ((start, end), mut points) in segments.iter().zip(split_points) {
+        let direction = *end - *start;
+
+        points.sort_by(|a, b| (*a - *start).dot(direction).total_cmp(&(*b - *start).dot(direction)));
+
+        let indices: Vec<usize> = points
+            .iter()
+            .map(|point| {
+                let key = (
+                    (point.x / SNAP_DISTANCE).round() as i64,
+                    (point.y / SNAP_DISTANCE).round() as i64,
+                );
+
+                *vertex_indices.entry(key).or_insert_with(|| {
+                    vertices.push(*point);
+
+                    vertices.len() - 1
+                })
+            })
+            .collect();
+
+        for pair in indices.windows(2) {
+            if pair[0] != pair[1] {
+                edges.insert((pair[0].min(pair[1]), pair[0].max(pair[1])));
+            }
+        }
+    }
+
+    let mut neighbors: Vec<Vec<usize>> = vec![vec![]; vertices.len()];
+
+    for (a, b) in edges {
+        neighbors[a].push(b);
+        neighbors[b].push(a);
+    }
+
+    // Remove the lines which lead nowhere, as they can not enclose any region.
+    let mut dead_ends: Vec<usize> = (0..vertices.len())
+        .filter(|vertex| neighbors[*vertex].len() == 1)
+        .collect();
+
+    while let Some(vertex) = dead_ends.pop() {
+        if let [neighbor] = neighbors[vertex][..] {
+            neighbors[vertex].clear();
+            neighbors[neighbor].retain(|other| *other != vertex);
+
+            if neighbors[neighbor].len() == 1 {
+                dead_ends.push(neighbor);
+            }
+        }
+    }
+
+    // Sort the neighbors of every vertex counter-clockwise.
+    for (vertex, vertex_neighbors) in neighbors.iter_mut().enumerate() {
+        vertex_neighbors.sort_by(|a, b| {
+            (vertices[*a] - vertices[vertex])
+                .to_angle()
+                .total_cmp(&(vertices[*b] - vertices[vertex]).to_angle())
+        });
+    }
+
+    // Find which connected group of lines every vertex belongs to.
+    let mut groups: Vec<Option<usize>> = vec![None; vertices.len()];
+
+    for vertex in 0..vertices.len() {
+        if groups[vertex].is_some() {
+            continue;
+        }
+
+        let mut stack = vec![vertex];
+
+        while let Some(current) = stack.pop() {
+            if groups[current].replace(vertex).is_none() {
+                stack.extend(neighbors[current].iter().filter(|other| groups[**other].is_none()));
+            }
+        }
+    }
+
+    // Walk around every face of the lines, always taking the sharpest turn to the left,
+    // ie. the neighbor right before the one we came from in the counter-clockwise order.
+    // This way the faces enclosed by the lines go around counter-clockwise and have a positive area,
+    // while the outer border of every group of lines goes around clockwise and has a negative area.
+    let mut visited: HashSet<(usize, usize)> = HashSet::new();
+    let mut faces: Vec<(Vec<Vec2>, f32, usize)> = vec![];
+
+    for vertex in 0..vertices.len() {
+        for neighbor in &neighbors[vertex] {
+            if visited.contains(&(vertex, *neighbor)) {
+                continue;
+            }
+
+            let mut face = vec![];
+            let (mut from, mut to) = (vertex, *neighbor);
+
+            while visited.insert((from, to)) {
+                face.push(vertices[from]);
+
+                let to_neighbors = &neighbors[to];
+                let from_idx = to_neighbors.iter().position(|other| *other == from).unwrap();
+                let next = to_neighbors[(from_idx + to_neighbors.len() - 1) % to_neighbors.len()];
+
+                (from, to) = (to, next);
+            }
+
+            let area = ring_area(&face);
+
+            faces.push((face, area, groups[vertex].unwrap()));
+        }
+    }
+
+    let point = geo_point(point);
+
+    let (outline, _, group) = faces
+        .iter()
+        .filter(|(face, area, _)| *area > POINT_EPSILON && geo_polygon(face).intersects(&point))
+        .min_by(|(_, a, _), (_, b, _)| a.total_cmp(b))?;
+
+    let region = geo_polygon(outline);
+
+    // The outer borders of the other groups inside of the region are its holes.
+    let hole_candidates: Vec<&Vec<Vec2>> = faces
+        .iter()
+        .filter(|(face, area, face_group)| {
+            *area < -POINT_EPSILON
+                && face_group != group
+                && region.contains(&geo_point(face[0]))
+        })
+        .map(|(face, _, _)| face)
+        .collect();
+
+    // Holes inside of other holes are not part of the region anyway.
+    let holes = hole_candidates
+        .iter()
+        .filter(|hole| {
+            !hole_candidates.iter().any(|other| {
+                !std::ptr::eq(**hole, *other) && geo_polygon(other).contains(&geo_point(hole[0]))
+            })
+        })
+        .map(|hole| hole.to_vec())
+        .collect();
+
+    Some((outline.clone(), holes))
+}
+
+/// A cubic bezier curve, going from `from` to `to`, shaped by its two control points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CubicBezier
+{
+    pub from: Vec2,
+    pub first_control: Vec2,
+    pub second_control: Vec2,
+    pub to: Vec2,
+}
+
+impl CubicBezier
+{
+    pub fn new(from: Vec2, first_control: Vec2, second_control: Vec2, to: Vec2) -> Self
+    {
+        Self {
+            from,
+            first_control,
+            second_control,
+            to,
+        }
+    }
+
+    /// Creates the cubic curve which has the exact same shape as the quadratic curve with the given control point.
+    pub fn from_quadratic(from: Vec2, control: Vec2, to: Vec2) -> Self
+    {
+        Self::new(
+            from,
+            from + (control - from) * (2. / 3.),
+            to + (control - to) * (2. / 3.),
+            to,
+        )
+    }
+
+    /// Creates the curves of a Catmull-Rom spline, which goes through every point of the list.
+    /// The first and the last points are repeated, so that the spline starts and ends at them.
+    pub fn catmull_rom(points: &[Vec2]) -> Vec<Self>
+    {
+        (0..points.len().saturating_sub(1))
+            .map(|idx| {
+                let previous = points[idx.saturating_sub(1)];
+                let from = points[idx];
+                let to = points[idx + 1];
+                let next = points[(idx + 2).min(points.len() - 1)];
+
+                Self::new(
+                    from,
+                    from + (to - previous) / 6.,
+                    to - (next - from) / 6.,
+                    to,
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the direction the curve is heading in at its end.
+    pub fn end_direction(&self) -> Vec2
+    {
+        // If a control point is at the end of the curve, the direction is given by the previous one.
+        [self.second_control, self.first_control, self.from]
+            .into_iter()
+            .map(|point| self.to - point)
+            .find(|direction| direction.length() > POINT_EPSILON)
+            .unwrap_or_default()
+    }
+
+    /// Returns whether the curve is close enough to the line between its ends to be drawn as a straight line.
+    fn is_flat(&self) -> bool
+    {
+        let chord = self.to - self.from;
+        let chord_length = chord.length();
+
+        // If the ends are at the same place, the control points have to be close to them.
+        if chord_length < POINT_EPSILON {
+            return self.from.distance(self.first_control) <= CURVE_TOLERANCE
+                && self.from.distance(self.second_control) <= CURVE_TOLERANCE;
+        }
+
+        let first_distance = (self.first_control - self.from).perp_dot(chord).abs() / chord_length;
+        let second_distance =
+            (self.second_control - self.from).perp_dot(chord).abs() / chord_length;
+
+        first_distance.max(second_distance) <= CURVE_TOLERANCE
+    }
+
+    /// Splits the curve into two halves with de Casteljau's algorithm.
+    fn split(&self) -> (Self, Self)
+    {
+        let from_first = self.from.midpoint(self.first_control);
+        let first_second = self.first_control.midpoint(self.second_control);
+        let second_to = self.second_control.midpoint(self.to);
+
+        let first_half_control = from_first.midpoint(first_second);
+        let second_half_control = first_second.midpoint(second_to);
+
+        let middle = first_half_control.midpoint(second_half_control);
+
+        (
+            Self::new(self.from, from_first, first_half_control, middle),
+            Self::new(middle, second_half_control, second_to, self.to),
+        )
+    }
+
+    /// Returns whether all of the points of the curve are finite, only these curves can be flattened.
+    pub fn is_finite(&self) -> bool
+    {
+        self.from.is_finite()
+            && self.first_control.is_finite()
+            && self.second_control.is_finite()
+            && self.to.is_finite()
+    }
+
+    /// Flattens the curve into a list of points, which can be connected with straight lines.
+    /// The curve is split up where it bends, and left in one piece where it is straight, see [`CURVE_TOLERANCE`].
+    /// The starting point of the curve is not included in the list, and the list is empty if the curve isn't finite.
+    pub fn flatten(&self) -> Vec<Vec2>
+    {
+        let mut points = vec![];
+
+        if !self.is_finite() {
+            return points;
+        }
+
+        self.flatten_into(&mut points, 0);
+
+        points
+    }
+
+    fn flatten_into(&self, points: &mut Vec<Vec2>, depth: u32)
+    {
+        if depth >= MAX_CURVE_SUBDIVISIONS || self.is_flat() {
+            points.push(self.to);
+
+            return;
+        }
+
+        let (first_half, second_half) = self.split();
+
+        first_half.flatten_into(points, depth + 1);
+        second_half.flatten_into(points, depth + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use bevy::color::Color;
+
+    use super::*;
+
+    /// Returns the area covered by the triangles, every triangle has to go around counter-clockwise.
+    fn triangulated_area(triangulation: &PolygonTriangulation) -> f32
+    {
+        triangulation
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|idx| triangulation.positions[triangle[idx] as usize]);
+                let area = (b - a).perp_dot(c - a) / 2.;
+
+                assert!(area > 0., "The triangle {a} {b} {c} isn't counter-clockwise.");
+
+                area
+            })
+            .sum()
+    }
+
+    fn points(points: &[(f32, f32)]) -> Vec<Vec2>
+    {
+        points.iter().map(|(x, y)| Vec2::new(*x, *y)).collect()
+    }
+
+    /// Creates a line strip through the points, every segment is 2 units wide.
+    fn line_strip(points: &[(f32, f32)], cap: LineCap, join: LineJoin) -> LineStrip
+    {
+        LineStrip::new(
+            points
+                .iter()
+                .map(|(x, y)| (Vec3::new(*x, *y, 0.), Color::WHITE, 2.))
+                .collect(),
+            cap,
+            join,
+        )
+    }
+
+    #[test]
+    fn tessellates_line_caps()
+    {
+        let butt = tessellate_line_strip(&line_strip(
+            &[(0., 0.), (10., 0.)],
+            LineCap::Butt,
+            LineJoin::Miter,
+        ));
+
+        assert_eq!(butt.positions.len(), 2 * 3);
+        assert_eq!(butt.colors.len(), butt.positions.len());
+
+        // The square caps extend the line by half of its width at both ends.
+        let square = tessellate_line_strip(&line_strip(
+            &[(0., 0.), (10., 0.)],
+            LineCap::Square,
+            LineJoin::Miter,
+        ));
+
+        assert_eq!(square.positions.len(), 3 * 2 * 3);
+        assert_eq!(
+            square
+                .positions
+                .iter()
+                .map(|position| position.x)
+                .fold(f32::MIN, f32::max),
+            11.
+        );
+        assert_eq!(
+            square
+                .positions
+                .iter()
+                .map(|position| position.x)
+                .fold(f32::MAX, f32::min),
+            -1.
+        );
+
+        // The round caps are half circles around the ends.
+        let round = tessellate_line_strip(&line_strip(
+            &[(0., 0.), (10., 0.)],
+            LineCap::Round,
+            LineJoin::Miter,
+        ));
+
+        assert_eq!(
+            round.positions.len(),
+            (2 + 2 * arc_step_count(1., PI) as usize) * 3
+        );
+        assert!(round.positions.iter().all(|position| {
+            position.truncate().distance(Vec2::ZERO) <= 1. + POINT_EPSILON
+                || position.truncate().distance(Vec2::new(10., 0.)) <= 1. + POINT_EPSILON
+                || (0. ..=10.).contains(&position.x)
+        }));
+    }
+
+    #[test]
+    fn miter_join_falls_back_to_bevel()
+    {
+        // A right angle stays within the miter limit, so the corner is filled with a quad.
+        let right_angle = tessellate_line_strip(&line_strip(
+            &[(0., 0.), (10., 0.), (10., 10.)],
+            LineCap::Butt,
+            LineJoin::Miter,
+        ));
+
+        assert_eq!(right_angle.positions.len(), (2 * 2 + 2) * 3);
+        assert!(right_angle
+            .positions
+            .iter()
+            .any(|position| position.truncate().distance(Vec2::new(11., -1.)) < POINT_EPSILON));
+
+        // The tip of this corner would be far past the miter limit, so it is cut off with a single triangle.
+        let sharp = tessellate_line_strip(&line_strip(
+            &[(0., 0.), (10., 0.), (0., 1.)],
+            LineCap::Butt,
+            LineJoin::Miter,
+        ));
+
+        assert_eq!(sharp.positions.len(), (2 * 2 + 1) * 3);
+        assert!(sharp
+            .positions
+            .iter()
+            .all(|position| position.x <= 10. + 1. + POINT_EPSILON));
+
+        let bevel = tessellate_line_strip(&line_strip(
+            &[(0., 0.), (10., 0.), (0., 1.)],
+            LineCap::Butt,
+            LineJoin::Bevel,
+        ));
+
+        assert_eq!(sharp, bevel);
+    }
+
+    #[test]
+    fn skips_zero_length_segments()
+    {
+        for cap in [LineCap::Butt, LineCap::Round, LineCap::Square] {
+            for join in [LineJoin::Miter, LineJoin::Round, LineJoin::Bevel] {
+                let line = tessellate_line_strip(&line_strip(
+                    &[(0., 0.), (0., 0.), (10., 0.), (10., 0.), (10., 10.)],
+                    cap,
+                    join,
+                ));
+
+                assert!(!line.positions.is_empty());
+                assert!(line.positions.iter().all(|position| position.is_finite()));
+
+                let point = tessellate_line_strip(&line_strip(&[(5., 5.), (5., 5.)], cap, join));
+
+                assert_eq!(point, TriangleBuffer::default());
+            }
+        }
+    }
+
+    #[test]
+    fn triangulates_convex_polygon()
+    {
+        let triangulation =
+            triangulate_polygon(&points(&[(0., 0.), (2., 0.), (2., 2.), (0., 2.)]), &[]);
+
+        assert_eq!(triangulation.indices.len(), 2 * 3);
+        assert!((triangulated_area(&triangulation) - 4.).abs() < POINT_EPSILON);
+    }
+
+    #[test]
+    fn triangulates_concave_polygon()
+    {
+        // An L shape, given clockwise.
+        let triangulation = triangulate_polygon(
+            &points(&[(0., 0.), (0., 2.), (1., 2.), (1., 1.), (2., 1.), (2., 0.)]),
+            &[],
+        );
+
+        assert_eq!(triangulation.indices.len(), 4 * 3);
+        assert!((triangulated_area(&triangulation) - 3.).abs() < POINT_EPSILON);
+    }
+
+    #[test]
+    fn triangulates_polygon_with_collinear_points()
+    {
+        let triangulation = triangulate_polygon(
+            &points(&[(0., 0.), (1., 0.), (2., 0.), (2., 1.), (2., 2.), (0., 2.), (0., 0.)]),
+            &[],
+        );
+
+        // The points on the edges are kept, but none of the triangles are flat.
+        assert_eq!(triangulation.indices.len(), 4 * 3);
+        assert!((triangulated_area(&triangulation) - 4.).abs() < POINT_EPSILON);
+
+        let line = triangulate_polygon(&points(&[(0., 0.), (1., 1.), (2., 2.)]), &[]);
+
+        assert_eq!(line, PolygonTriangulation::default());
+    }
+
+    #[test]
+    fn triangulates_polygon_with_hole()
+    {
+        let triangulation = triangulate_polygon(
+            &points(&[(0., 0.), (4., 0.), (4., 4.), (0., 4.)]),
+            &[points(&[(1., 1.), (3., 1.), (3., 3.), (1., 3.)])],
+        );
+
+        // The bridge repeats two of the points, so the ring has ten points.
+        assert_eq!(triangulation.indices.len(), 8 * 3);
+        assert!((triangulated_area(&triangulation) - 12.).abs() < POINT_EPSILON);
+    }
+
+    #[test]
+    fn triangulates_self_touching_polygon()
+    {
+        // Two squares which touch at a single corner.
+        let triangulation = triangulate_polygon(
+            &points(&[
+                (0., 0.),
+                (1., 0.),
+                (1., 1.),
+                (2., 1.),
+                (2., 2.),
+                (1., 2.),
+                (1., 1.),
+                (0., 1.),
+            ]),
+            &[],
+        );
+
+        assert_eq!(triangulation.indices.len(), 4 * 3);
+        assert!((triangulated_area(&triangulation) - 2.).abs() < POINT_EPSILON);
+    }
+
+    fn square(min: (f32, f32), max: (f32, f32)) -> Vec<Line>
+    {
+        let corners = points(&[min, (max.0, min.1), max, (min.0, max.1)]);
+
+        (0..4)
+            .map(|idx| Line::new(corners[idx].extend(0.), corners[(idx + 1) % 4].extend(0.)))
+            .collect()
+    }
+
+    #[test]
+    fn finds_nested_region()
+    {
+        let lines = [square((0., 0.), (4., 4.)), square((1., 1.), (2., 2.))].concat();
+
+        let (outline, holes) = enclosing_region(&lines, Vec2::new(1.5, 1.5)).unwrap();
+
+        assert!((ring_area(&outline) - 1.).abs() < POINT_EPSILON);
+        assert!(holes.is_empty());
+    }
+
+    #[test]
+    fn finds_region_with_hole()
+    {
+        let lines = [square((0., 0.), (4., 4.)), square((1., 1.), (2., 2.))].concat();
+
+        let (outline, holes) = enclosing_region(&lines, Vec2::new(3., 3.)).unwrap();
+
+        assert!((ring_area(&outline) - 16.).abs() < POINT_EPSILON);
+        assert_eq!(holes.len(), 1);
+        assert!((ring_area(&holes[0]).abs() - 1.).abs() < POINT_EPSILON);
+    }
+
+    #[test]
+    fn finds_region_of_crossing_lines()
+    {
+        // A hash sign, the lines only enclose the square in its middle.
+        let lines: Vec<Line> = [
+            ((1., 0.), (1., 3.)),
+            ((2., 0.), (2., 3.)),
+            ((0., 1.), (3., 1.)),
+            ((0., 2.), (3., 2.)),
+        ]
+        .iter()
+        .map(|((x1, y1), (x2, y2))| Line::new(Vec3::new(*x1, *y1, 0.), Vec3::new(*x2, *y2, 0.)))
+        .collect();
+
+        let (outline, holes) = enclosing_region(&lines, Vec2::new(1.5, 1.5)).unwrap();
+
+        assert!((ring_area(&outline) - 1.).abs() < POINT_EPSILON);
+        assert!(holes.is_empty());
+    }
+
+    #[test]
+    fn finds_no_region_outside()
+    {
+        let lines = [square((0., 0.), (4., 4.)), square((1., 1.), (2., 2.))].concat();
+
+        assert!(enclosing_region(&lines, Vec2::new(5., 5.)).is_none());
+        assert!(enclosing_region(&lines[..3], Vec2::new(3., 3.)).is_none());
+    }
+}
@@ -2,12 +2,19 @@
 
 pub const DEMO_FILE_EXTENSION: &str = "demo";
 pub const PROJECT_FILE_EXTENSION: &str = "save";
+/// The width of the lines drawn by a newly created [`Drawer`].
+pub const DEFAULT_PEN_SIZE: f32 = 1.;
+/// The longest time a script can wait for at once, in seconds.
+pub const MAX_WAIT_SECONDS: f32 = 3600.;
+/// How many times the `on_fixed_tick` callbacks are called every second.
+pub const FIXED_TICKS_PER_SECOND: f64 = 60.;
 
 use bevy::{
-    asset::RenderAssetUsages,
+    asset::{Handle, RenderAssetUsages},
     color::Color,
+    image::{CompressedImageFormats, ImageSampler, ImageType, TextureError},
     math::{Vec2, Vec3, Vec4},
-    prelude::{Component, Mesh, Res, ResMut, Resource},
+    prelude::{Component, Image, Mesh, ResMut, Resource},
     render::mesh::PrimitiveTopology,
 };
 
@@ -17,21 +24,26 @@ use fragile::Fragile;
 use piccolo::{error::LuaError, Callback, RuntimeError, Value};
 
 use std::{
-    collections::VecDeque, fmt::Display, ops::{Deref, DerefMut}, sync::{
-        mpsc::{channel, Receiver, Sender},
-        Arc,
-    }
+    collections::{BTreeMap, HashMap, VecDeque}, fmt::Display, ops::{Deref, DerefMut}, str::FromStr, sync::{atomic::{AtomicU64, AtomicUsize, Ordering}, Arc}
 };
-use strum::{EnumCount, EnumIter};
+#[cfg(not(target_family = "wasm"))]
+use std::time::{Duration, Instant};
+use strum::{EnumCount, EnumIter, EnumString};
 
+pub mod colors;
+pub mod geometry;
+pub mod parameters;
+#[cfg(not(target_family = "wasm"))]
+pub mod tasks;
 pub mod ui;
 use chrono::{DateTime, Local};
+use colors::{color_into_srgba, hsv_color, Palette};
 use dashmap::DashMap;
 use egui_toast::{Toast, Toasts};
-use geo::{coord, point, Contains, ConvexHull, Coord, LineString, Polygon};
+use geometry::CubicBezier;
 
 #[cfg(not(target_family = "wasm"))]
-use mlua::{Error, Function};
+use mlua::{Error, Function, Variadic};
 
 use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
@@ -129,10 +141,41 @@ pub enum DemoStep
     Disable(String),
     Enable(String),
     Fill(String),
+    BeginFill(String),
+    EndFill(String),
+    FillColor(String, Option<[NonNaN<f32>; 4]>),
     Rectangle(String, NonNaN<f32>, NonNaN<f32>),
     Print(String),
     Loop(usize, Vec<DemoStep>),
     PointTo(String, f32, f32),
+    PenSize(String, NonNaN<f32>),
+    LineCap(String, LineCap),
+    LineJoin(String, LineJoin),
+    Arc(String, NonNaN<f32>, NonNaN<f32>, bool),
+    Circle(String, NonNaN<f32>, bool),
+    Ellipse(String, NonNaN<f32>, NonNaN<f32>, bool),
+    CurveTo(String, NonNaN<f32>, NonNaN<f32>, NonNaN<f32>, NonNaN<f32>),
+    CubicTo(
+        String,
+        NonNaN<f32>,
+        NonNaN<f32>,
+        NonNaN<f32>,
+        NonNaN<f32>,
+        NonNaN<f32>,
+        NonNaN<f32>,
+    ),
+    Spline(String, Vec<(NonNaN<f32>, NonNaN<f32>)>),
+    Write(String, String, NonNaN<f32>),
+    SetCostume(String, Option<String>),
+    Stamp(String),
+    PenUp(String),
+    PenDown(String),
+    Hide(String),
+    Show(String),
+    GoTo(String, NonNaN<f32>, NonNaN<f32>),
+    SetX(String, NonNaN<f32>),
+    SetY(String, NonNaN<f32>),
+    Home(String),
 }
 
 impl DemoStep
@@ -171,11 +214,23 @@ impl Display for DemoStep
                 format!(r#"disable("{id}")"#)
             },
             DemoStep::Enable(id) => {
-                format!(r#"disable("{id}")"#)
+                format!(r#"enable("{id}")"#)
             },
             DemoStep::Fill(id) => {
                 format!(r#"fill("{id}")"#)
             },
+            DemoStep::BeginFill(id) => {
+                format!(r#"begin_fill("{id}")"#)
+            },
+            DemoStep::EndFill(id) => {
+                format!(r#"end_fill("{id}")"#)
+            },
+            DemoStep::FillColor(id, color) => {
+                match color {
+                    Some([r, g, b, a]) => format!(r#"fill_color("{id}", {r}, {g}, {b}, {a})"#),
+                    None => format!(r#"fill_color("{id}")"#),
+                }
+            },
             DemoStep::Rectangle(id, desired_x, desired_y) => {
                 format!(r#"rectangle("{id}", {desired_x}, {desired_y})"#)
             },
@@ -195,29 +250,105 @@ impl Display for DemoStep
             },
             DemoStep::PointTo(id, dx, dy) => {
                 format!(r#"point_to("{id}", {dx}, {dy})"#)
-            }
+            },
+            DemoStep::PenSize(id, width) => {
+                format!(r#"pen_size("{id}", {width})"#)
+            },
+            DemoStep::LineCap(id, cap) => {
+                format!(r#"line_cap("{id}", "{cap}")"#)
+            },
+            DemoStep::LineJoin(id, join) => {
+                format!(r#"line_join("{id}", "{join}")"#)
+            },
+            DemoStep::Arc(id, radius, degrees, filled) => {
+                format!(r#"arc("{id}", {radius}, {degrees}, {filled})"#)
+            },
+            DemoStep::Circle(id, radius, filled) => {
+                format!(r#"circle("{id}", {radius}, {filled})"#)
+            },
+            DemoStep::Ellipse(id, radius_x, radius_y, filled) => {
+                format!(r#"ellipse("{id}", {radius_x}, {radius_y}, {filled})"#)
+            },
+            DemoStep::CurveTo(id, control_x, control_y, x, y) => {
+                format!(r#"curve_to("{id}", {control_x}, {control_y}, {x}, {y})"#)
+            },
+            DemoStep::CubicTo(id, first_x, first_y, second_x, second_y, x, y) => {
+                format!(
+                    r#"cubic_to("{id}", {first_x}, {first_y}, {second_x}, {second_y}, {x}, {y})"#
+                )
+            },
+            DemoStep::Spline(id, points) => {
+                format!(r#"spline("{id}", {{{}}})"#, {
+                    points
+                        .iter()
+                        .map(|(x, y)| format!("{{{x}, {y}}}"))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                })
+            },
+            DemoStep::Write(id, text, size) => {
+                // The debug format of the text is a quoted and escaped string.
+                format!(r#"write("{id}", {text:?}, {size})"#)
+            },
+            DemoStep::SetCostume(id, costume) => {
+                match costume {
+                    Some(name) => format!(r#"set_costume("{id}", {name:?})"#),
+                    None => format!(r#"set_costume("{id}", nil)"#),
+                }
+            },
+            DemoStep::Stamp(id) => {
+                format!(r#"stamp("{id}")"#)
+            },
+            DemoStep::PenUp(id) => {
+                format!(r#"pen_up("{id}")"#)
+            },
+            DemoStep::PenDown(id) => {
+                format!(r#"pen_down("{id}")"#)
+            },
+            DemoStep::Hide(id) => {
+                format!(r#"hide("{id}")"#)
+            },
+            DemoStep::Show(id) => {
+                format!(r#"show("{id}")"#)
+            },
+            DemoStep::GoTo(id, x, y) => {
+                format!(r#"go_to("{id}", {x}, {y})"#)
+            },
+            DemoStep::SetX(id, x) => {
+                format!(r#"set_x("{id}", {x})"#)
+            },
+            DemoStep::SetY(id, y) => {
+                format!(r#"set_y("{id}", {y})"#)
+            },
+            DemoStep::Home(id) => {
+                format!(r#"home("{id}")"#)
+            },
         })
     }
 }
 
-#[derive(Resource, Clone)]
-pub struct DrawRequester
-{
-    pub receiver: Arc<Mutex<Receiver<(Vec<Vec3>, Color, String)>>>,
-    pub sender: Arc<Sender<(Vec<Vec3>, Color, String)>>,
-}
+/// The functions of the `os` library which can't access the files or the processes of the computer.
+#[cfg(not(target_family = "wasm"))]
+const SAFE_OS_FUNCTIONS: &[&str] = &["clock", "date", "difftime", "time"];
 
-impl Default for DrawRequester
-{
-    fn default() -> Self
-    {
-        let (sender, receiver) = channel::<(Vec<Vec3>, Color, String)>();
-        Self {
-            sender: Arc::new(sender),
-            receiver: Arc::new(Mutex::new(receiver)),
-        }
-    }
-}
+/// The global functions which read files, these are only available in trusted projects.
+#[cfg(not(target_family = "wasm"))]
+const FILE_FUNCTIONS: &[&str] = &["dofile", "loadfile", "require"];
+
+/// The global functions which load lua code, these can only load bytecode in trusted projects.
+#[cfg(not(target_family = "wasm"))]
+const LOAD_FUNCTIONS: &[&str] = &["load", "loadstring"];
+
+/// Wraps one of the `LOAD_FUNCTIONS`, so that it refuses to load bytecode.
+/// LuaJIT doesn't verify the bytecode it loads, and hand-crafted bytecode can escape the sandbox.
+#[cfg(not(target_family = "wasm"))]
+const TEXT_ONLY_LOAD: &str = r#"
+    local load = ...
+
+    return function(chunk, chunkname, _, env)
+        return load(chunk, chunkname, "t", env)
+    end
+"#;
 
 #[derive(Resource, Clone)]
 pub struct LuaRuntime(
@@ -230,7 +361,41 @@ impl Default for LuaRuntime
     fn default() -> Self
     {
         #[cfg(not(target_family = "wasm"))]
-        return Self(unsafe { mlua::Lua::unsafe_new() });
+        {
+            // Only the standard libraries which can't reach outside of the app are loaded, along with `package` and `os`.
+            // The `package` library is removed and the `os` library is sanitized to the `SAFE_OS_FUNCTIONS` by `set_trusted`.
+            let std_libs = mlua::StdLib::TABLE
+                | mlua::StdLib::STRING
+                | mlua::StdLib::MATH
+                | mlua::StdLib::BIT
+                | mlua::StdLib::JIT
+                | mlua::StdLib::PACKAGE
+                | mlua::StdLib::OS;
+
+            let lua = mlua::Lua::new_with(std_libs, mlua::LuaOptions::default()).unwrap();
+
+            // The functions are stored, so that they can be restored if the project is trusted.
+            // Loading the `package` library again doesn't bring `require` back, as the library is already loaded.
+            for name in FILE_FUNCTIONS.iter().chain(LOAD_FUNCTIONS) {
+                let function = lua.globals().get::<mlua::Value>(*name).unwrap();
+
+                lua.set_named_registry_value(name, function).unwrap();
+            }
+
+            let dump = lua
+                .globals()
+                .get::<mlua::Table>("string")
+                .and_then(|string| string.get::<mlua::Value>("dump"))
+                .unwrap();
+
+            lua.set_named_registry_value("string.dump", dump).unwrap();
+
+            let lua_runtime = Self(lua);
+
+            lua_runtime.set_trusted(false).unwrap();
+
+            lua_runtime
+        }
 
         #[cfg(target_family = "wasm")]
         {
@@ -245,10 +410,71 @@ impl Default for LuaRuntime
 
 impl LuaRuntime
 {
+    /// Sets whether the scripts can access the files of the computer.
+    /// Trusted scripts can use the `io` and `os` libraries, `dofile`, `loadfile` and `require` external modules, these are removed otherwise.
+    /// Untrusted scripts also can't dump functions into bytecode with `string.dump`, and `load` and `loadstring` only load source code.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn set_trusted(&self, trusted: bool) -> mlua::Result<()>
+    {
+        let globals = self.globals();
+        let string: mlua::Table = globals.get("string")?;
+
+        // Load a fresh copy of the libraries, in case a script has modified them.
+        self.load_std_libs(mlua::StdLib::OS)?;
+
+        // The jit library stays in the loaded modules after it is removed by the execution limits, `require` would hand it back to the scripts.
+        self.named_registry_value::<mlua::Table>("_LOADED")?
+            .set("jit", mlua::Value::Nil)?;
+
+        if trusted {
+            self.load_std_libs(mlua::StdLib::IO | mlua::StdLib::PACKAGE)?;
+
+            for name in FILE_FUNCTIONS.iter().chain(LOAD_FUNCTIONS) {
+                globals.set(*name, self.named_registry_value::<mlua::Value>(name)?)?;
+            }
+
+            string.set(
+                "dump",
+                self.named_registry_value::<mlua::Value>("string.dump")?,
+            )?;
+
+            return Ok(());
+        }
+
+        for name in ["io", "package"].iter().chain(FILE_FUNCTIONS) {
+            globals.set(*name, mlua::Value::Nil)?;
+        }
+
+        for name in LOAD_FUNCTIONS {
+            let load = self
+                .load(TEXT_ONLY_LOAD)
+                .call::<mlua::Function>(self.named_registry_value::<mlua::Function>(name)?)?;
+
+            globals.set(*name, load)?;
+        }
+
+        string.set("dump", mlua::Value::Nil)?;
+
+        let os: mlua::Table = globals.get("os")?;
+        let safe_os = self.create_table()?;
+
+        for name in SAFE_OS_FUNCTIONS {
+            safe_os.set(*name, os.get::<mlua::Value>(*name)?)?;
+        }
+
+        globals.set("os", safe_os)?;
+
+        Ok(())
+    }
+
     pub fn execute_code(&self, code: &str) -> anyhow::Result<()>
     {
         #[cfg(not(target_family = "wasm"))]
-        self.load(code).exec()?;
+        {
+            tasks::restart_execution_budget(self);
+
+            self.load(code).exec()?;
+        }
 
         #[cfg(target_family = "wasm")]
         {
@@ -270,6 +496,17 @@ impl LuaRuntime
 
         Ok(())
     }
+
+    /// Executes the code in the environment, the global variables set by the code are set in the environment instead of the global table.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn execute_code_in(&self, code: &str, environment: mlua::Table) -> anyhow::Result<()>
+    {
+        tasks::restart_execution_budget(self);
+
+        self.load(code).set_environment(environment).exec()?;
+
+        Ok(())
+    }
 }
 
 impl Deref for LuaRuntime
@@ -349,18 +586,163 @@ pub enum ScriptLinePrompts
 #[derive(Component)]
 pub struct DrawerMesh;
 
+/// The shape drawn at the two open ends of a [`LineStrip`].
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, EnumString, strum::Display,
+)]
+#[strum(serialize_all = "snake_case", ascii_case_insensitive)]
+pub enum LineCap
+{
+    /// The line ends exactly at its end points.
+    #[default]
+    Butt,
+    /// The line ends with a half circle around its end points.
+    Round,
+    /// The line is extended past its end points by half of its width.
+    Square,
+}
+
+/// The shape drawn where two segments of a [`LineStrip`] meet.
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, EnumString, strum::Display,
+)]
+#[strum(serialize_all = "snake_case", ascii_case_insensitive)]
+pub enum LineJoin
+{
+    /// The outer edges of the segments are extended until they meet.
+    /// Falls back to [`LineJoin::Bevel`] if the corner is too sharp, see [`geometry::MITER_LIMIT`].
+    #[default]
+    Miter,
+    /// The corner is rounded off with a circular arc.
+    Round,
+    /// The corner is cut off with a straight line.
+    Bevel,
+}
+
+/// How fast the drawers walk along the lines they draw.
+/// The speed is a level ranging from `0` to [`AnimationSpeed::MAX`], `0` means that the drawers move instantly, higher levels are slower.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AnimationSpeed(pub u8);
+
+impl AnimationSpeed
+{
+    /// The slowest level.
+    pub const MAX: u8 = 10;
+
+    /// The distance walked every second at the fastest level.
+    const FASTEST_UNITS_PER_SECOND: f32 = 1500.;
+
+    /// Returns the distance the drawers walk every second, or [`None`] if they move instantly.
+    pub fn units_per_second(self) -> Option<f32>
+    {
+        if self.0 == 0 {
+            return None;
+        }
+
+        // Every level is 40% slower than the previous one.
+        Some(Self::FASTEST_UNITS_PER_SECOND * 0.6_f32.powi(self.0.min(Self::MAX) as i32 - 1))
+    }
+}
+
+/// The limits of how long the lua code can run at once without pausing, and how much it can draw.
+/// The lua code is stopped with an error if it exceeds any of the limits, so that a runaway script can't freeze the app.
+/// A limit of `0` means that the limit is disabled.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ExecutionLimits
+{
+    /// The longest time the lua code can run at once, in seconds.
+    pub max_seconds: f32,
+    /// The most instructions the lua code can execute at once.
+    pub max_instructions: u64,
+    /// The most memory the lua runtime can use, in megabytes.
+    pub max_memory_megabytes: u64,
+    /// The most drawers which can exist at once.
+    pub max_drawers: usize,
+    /// The most points the drawings of all the drawers can contain, every text and stamp counts as one point, see [`Drawers::point_count`].
+    pub max_points: usize,
+}
+
+impl Default for ExecutionLimits
+{
+    fn default() -> Self
+    {
+        Self {
+            max_seconds: 5.,
+            max_instructions: 1_000_000_000,
+            max_memory_megabytes: 512,
+            max_drawers: 1000,
+            max_points: 1_000_000,
+        }
+    }
+}
+
+/// A point of a [`LineStrip`], containing its position, its color and the width of the line drawn to it.
+pub type LinePoint = (Vec3, Color, f32);
+
 /// A list of points that will have a line drawn between each consecutive points
-#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LineStrip
 {
-    pub points: Vec<(Vec3, Color)>,
+    /// The points of the line, these should be added with [`LineStrip::extend`] so that the line is redrawn.
+    pub points: Vec<LinePoint>,
+    /// The shape of the line's ends.
+    pub cap: LineCap,
+    /// The shape of the line's corners.
+    pub join: LineJoin,
+
+    /// Identifies the state of the line, the canvas only rebuilds the line's mesh if this has changed since it was last drawn.
+    #[serde(skip, default = "next_revision")]
+    revision: u64,
+}
+
+impl Default for LineStrip
+{
+    fn default() -> Self
+    {
+        Self::new(vec![], LineCap::default(), LineJoin::default())
+    }
+}
+
+/// The lines are compared by their contents, the revision only tells the canvas whether to rebuild the mesh.
+impl PartialEq for LineStrip
+{
+    fn eq(&self, other: &Self) -> bool
+    {
+        self.points == other.points && self.cap == other.cap && self.join == other.join
+    }
 }
 
 impl LineStrip
 {
-    pub fn new(points: Vec<(Vec3, Color)>) -> Self
+    pub fn new(points: Vec<LinePoint>, cap: LineCap, join: LineJoin) -> Self
+    {
+        Self {
+            points,
+            cap,
+            join,
+            revision: next_revision(),
+        }
+    }
+
+    /// Returns the revision of the line, which changes every time points are added to it.
+    pub fn revision(&self) -> u64
+    {
+        self.revision
+    }
+
+    /// Adds the points to the end of the line.
+    pub fn extend(&mut self, points: impl IntoIterator<Item = LinePoint>)
     {
-        Self { points }
+        self.points.extend(points);
+        self.revision = next_revision();
+    }
+
+    /// Removes the last point of the line.
+    pub fn pop(&mut self) -> Option<LinePoint>
+    {
+        self.revision = next_revision();
+        self.points.pop()
     }
 }
 
@@ -368,78 +750,191 @@ impl From<LineStrip> for Mesh
 {
     fn from(line: LineStrip) -> Self
     {
+        // Turn the line into triangles, so that its width, caps and joins can be displayed
+        let triangles = geometry::tessellate_line_strip(&line);
+
         Mesh::new(
-            // This tells wgpu that the positions are a list of points
-            // where a line will be drawn between each consecutive point
-            PrimitiveTopology::LineStrip,
+            // This tells wgpu that every three consecutive positions make up a triangle
+            PrimitiveTopology::TriangleList,
             RenderAssetUsages::RENDER_WORLD,
         )
         // Add the point positions as an attribute
-        .with_inserted_attribute(
-            Mesh::ATTRIBUTE_POSITION,
-            line.points
-                .iter()
-                .map(|point| point.0)
-                .collect::<Vec<Vec3>>(),
-        )
-        .with_inserted_attribute(
-            Mesh::ATTRIBUTE_COLOR,
-            line.points
-                .iter()
-                .map(|point| color_into_vec4(point.1))
-                .collect::<Vec<Vec4>>(),
-        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, triangles.positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, triangles.colors)
     }
 }
 
 /// A list of points that will have polygon created from them
-#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FilledPolygonPoints
 {
     /// The points of the polygon.
     pub points: Vec<Vec3>,
     /// The color of the polygon.
     pub color: Color,
+    /// The points of the holes cut out of the polygon.
+    #[serde(default)]
+    pub holes: Vec<Vec<Vec3>>,
+
+    /// Identifies the polygon, the canvas only builds the polygon's mesh if it hasn't drawn this revision yet.
+    #[serde(skip, default = "next_revision")]
+    revision: u64,
+}
+
+impl Default for FilledPolygonPoints
+{
+    fn default() -> Self
+    {
+        Self::new(vec![], Color::default())
+    }
+}
+
+/// The polygons are compared by their contents, the revision only tells the canvas whether to rebuild the mesh.
+impl PartialEq for FilledPolygonPoints
+{
+    fn eq(&self, other: &Self) -> bool
+    {
+        self.points == other.points && self.color == other.color && self.holes == other.holes
+    }
 }
 
 impl FilledPolygonPoints
 {
+    /// Returns the number of points of the polygon, including the points of its holes.
+    pub fn point_count(&self) -> usize
+    {
+        self.points.len() + self.holes.iter().map(Vec::len).sum::<usize>()
+    }
+
     pub fn new(points: Vec<Vec3>, color: Color) -> Self
     {
-        Self { points, color }
+        Self {
+            points,
+            color,
+            holes: vec![],
+            revision: next_revision(),
+        }
+    }
+
+    /// Returns the revision of the polygon, which identifies it on the canvas.
+    pub fn revision(&self) -> u64
+    {
+        self.revision
+    }
+
+    /// Sets the holes cut out of the polygon.
+    pub fn with_holes(mut self, holes: Vec<Vec<Vec3>>) -> Self
+    {
+        self.holes = holes;
+        self.revision = next_revision();
+
+        self
     }
 }
 
 impl From<FilledPolygonPoints> for Mesh
 {
-    fn from(line: FilledPolygonPoints) -> Self
+    fn from(polygon: FilledPolygonPoints) -> Self
     {
-        let mut indices = vec![];
+        let holes: Vec<Vec<Vec2>> = polygon
+            .holes
+            .iter()
+            .map(|hole| hole.iter().map(|point| point.truncate()).collect())
+            .collect();
+
+        // Turn the polygon into triangles, so that concave polygons and polygons with holes are displayed correctly
+        let triangulation = geometry::triangulate_polygon(
+            &polygon
+                .points
+                .iter()
+                .map(|point| point.truncate())
+                .collect::<Vec<Vec2>>(),
+            &holes,
+        );
 
-        for i in 1..line.points.len() - 1 {
-            indices.push(0);
-            indices.push(i as u32);
-            indices.push((i + 1) as u32);
-        }
+        let vertex_count = triangulation.positions.len();
 
         let mut mesh = Mesh::new(
-            bevy::render::mesh::PrimitiveTopology::TriangleStrip,
+            // This tells wgpu that every three consecutive indices make up a triangle
+            PrimitiveTopology::TriangleList,
             RenderAssetUsages::RENDER_WORLD,
         )
         // Add the point positions as an attribute
-        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, line.points.to_vec())
         .with_inserted_attribute(
-            Mesh::ATTRIBUTE_NORMAL,
-            vec![[0., 0., 1.]; line.points.len()],
+            Mesh::ATTRIBUTE_POSITION,
+            triangulation
+                .positions
+                .iter()
+                .map(|point| point.extend(0.))
+                .collect::<Vec<Vec3>>(),
         )
-        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0., 0.]; line.points.len()]);
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 0., 1.]; vertex_count])
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0., 0.]; vertex_count])
+        .with_inserted_attribute(
+            Mesh::ATTRIBUTE_COLOR,
+            vec![color_into_vec4(polygon.color); vertex_count],
+        );
 
-        mesh.insert_indices(bevy::render::mesh::Indices::U32(indices));
+        mesh.insert_indices(bevy::render::mesh::Indices::U32(triangulation.indices));
 
         mesh
     }
 }
 
+/// A text written onto the canvas by a drawer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextLabel
+{
+    /// The text itself.
+    pub text: String,
+    /// The position of the text's bottom left corner.
+    pub pos: Vec2,
+    /// The angle of the drawer when the text was written, the text is horizontal if the drawer was pointing upwards.
+    pub ang: Angle,
+    /// The font size of the text.
+    pub size: f32,
+    /// The color of the text.
+    pub color: Color,
+
+    /// Identifies the text, the canvas only spawns the text if it hasn't displayed this revision yet.
+    #[serde(skip, default = "next_revision")]
+    revision: u64,
+}
+
+/// The texts are compared by their contents, the revision only tells the canvas whether to respawn the text.
+impl PartialEq for TextLabel
+{
+    fn eq(&self, other: &Self) -> bool
+    {
+        self.text == other.text
+            && self.pos == other.pos
+            && self.ang == other.ang
+            && self.size == other.size
+            && self.color == other.color
+    }
+}
+
+impl TextLabel
+{
+    pub fn new(text: String, pos: Vec2, ang: Angle, size: f32, color: Color) -> Self
+    {
+        Self {
+            text,
+            pos,
+            ang,
+            size,
+            color,
+            revision: next_revision(),
+        }
+    }
+
+    /// Returns the revision of the text, which identifies it on the canvas.
+    pub fn revision(&self) -> u64
+    {
+        self.revision
+    }
+}
+
 pub fn color_into_vec4(color: Color) -> Vec4
 {
     Vec4::new(
@@ -496,139 +991,2012 @@ impl<'de> Deserialize<'de> for Angle
     }
 }
 
-/// The information of the Drawer
-#[derive(Resource, Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct Drawer
+/// A copy of a drawer's sprite left on the canvas.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Stamp
 {
-    /// Whether the Drawer should draw.
-    pub enabled: bool,
-
-    /// The position of the Drawer.
+    /// The name of the [`ProjectImage`] the stamp displays, if this is [`None`] it displays Ferris.
+    pub costume: Option<String>,
+    /// The position of the stamp.
     pub pos: Vec2,
-
-    /// The angle of the Drawer.
+    /// The angle of the drawer when the stamp was made.
     pub ang: Angle,
 
-    /// The line drawn by the drawer.
-    pub drawings: Drawings,
-
-    /// The color of the Drawer.
-    pub color: Color,
+    /// Identifies the stamp, the canvas only spawns the stamp if it hasn't displayed this revision yet.
+    #[serde(skip, default = "next_revision")]
+    revision: u64,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
-pub struct Drawings
+/// The stamps are compared by their contents, the revision only tells the canvas whether to respawn the stamp.
+impl PartialEq for Stamp
 {
-    /// The lines drawn by the drawer.
-    pub lines: Vec<LineStrip>,
-    /// The polygons drawn by the drawer.
-    pub polygons: Vec<FilledPolygonPoints>,
+    fn eq(&self, other: &Self) -> bool
+    {
+        self.costume == other.costume && self.pos == other.pos && self.ang == other.ang
+    }
 }
 
-impl Default for Drawings
+impl Stamp
 {
-    fn default() -> Self
+    pub fn new(costume: Option<String>, pos: Vec2, ang: Angle) -> Self
     {
         Self {
-            lines: vec![LineStrip::new(vec![(Vec3::default(), Color::WHITE)])],
-            polygons: vec![],
+            costume,
+            pos,
+            ang,
+            revision: next_revision(),
         }
     }
+
+    /// Returns the revision of the stamp, which identifies it on the canvas.
+    pub fn revision(&self) -> u64
+    {
+        self.revision
+    }
 }
 
-#[derive(Clone, Debug)]
-pub enum DrawingType
+/// An image stored in the project, which can be used as a drawer's costume.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProjectImage
 {
-    Line(LineStrip),
-    Polygon(FilledPolygonPoints),
+    /// The file extension of the image, this is used to decide how the image should be decoded.
+    pub extension: String,
+    /// The encoded image.
+    pub bytes: Arc<[u8]>,
 }
 
-impl Default for Drawer
+impl ProjectImage
 {
-    fn default() -> Self
+    pub fn new(extension: String, bytes: Arc<[u8]>) -> Self
     {
-        Self {
-            enabled: true,
-            pos: Vec2::default(),
-            ang: Angle::from_degrees(90.),
-            drawings: Drawings::default(),
-            color: Color::WHITE,
-        }
+        Self { extension, bytes }
+    }
+
+    /// Decodes the image, so that it can be displayed by bevy.
+    pub fn decode(&self) -> Result<Image, TextureError>
+    {
+        Image::from_buffer(
+            &self.bytes,
+            ImageType::Extension(&self.extension),
+            CompressedImageFormats::NONE,
+            true,
+            ImageSampler::Default,
+            RenderAssetUsages::RENDER_WORLD,
+        )
     }
 }
 
-/// The list of the drawers currently alive.
-/// This list is modified through the [`Lua`] runtime.
-/// The key is a [`String`] is used to identify each individual [`Drawer`].
-#[derive(Resource, Default, Debug, Clone)]
-pub struct Drawers(pub Arc<DashMap<String, Drawer>>);
+/// The list of images stored in the project, the images are identified by their name.
+/// The list keeps track of its modifications, so that the decoded images can be kept up to date.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageLibrary
+{
+    images: BTreeMap<String, ProjectImage>,
 
-/// Implement dereferencing for the [`Drawers`] type.
-impl Deref for Drawers
+    /// This is incremented every time the list is modified.
+    #[serde(skip)]
+    revision: u64,
+}
+
+impl ImageLibrary
 {
-    type Target = Arc<DashMap<String, Drawer>>;
+    /// Returns the images by their names.
+    pub fn images(&self) -> &BTreeMap<String, ProjectImage>
+    {
+        &self.images
+    }
 
-    fn deref(&self) -> &Self::Target
+    /// Returns the number of modifications made to the list.
+    pub fn revision(&self) -> u64
     {
-        &self.0
+        self.revision
+    }
+
+    pub fn contains(&self, name: &str) -> bool
+    {
+        self.images.contains_key(name)
+    }
+
+    /// Inserts an image into the list, this overwrites the image with the same name.
+    pub fn insert(&mut self, name: String, image: ProjectImage)
+    {
+        self.images.insert(name, image);
+        self.revision += 1;
+    }
+
+    /// Removes the image with the given name from the list.
+    pub fn remove(&mut self, name: &str) -> Option<ProjectImage>
+    {
+        self.revision += 1;
+        self.images.remove(name)
+    }
+
+    /// Replaces all of the images with the ones from the other list, ie. when opening a project.
+    pub fn replace(&mut self, other: ImageLibrary)
+    {
+        self.images = other.images;
+        self.revision += 1;
     }
 }
 
-/// Implement mutable dereferencing for the [`Drawers`] type.
-impl DerefMut for Drawers
+/// The decoded images of the [`ImageLibrary`], which are used to display the drawers' costumes.
+#[derive(Resource, Default)]
+pub struct Costumes
 {
-    fn deref_mut(&mut self) -> &mut Self::Target
+    /// The revision of the [`ImageLibrary`] the images were decoded from.
+    pub revision: Option<u64>,
+    /// The handles and the sizes of the decoded images by their names.
+    pub handles: HashMap<String, (Handle<Image>, Vec2)>,
+}
+
+/// The information of the Drawer
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct Drawer
+{
+    /// Whether the Drawer should draw, ie. if its pen is down.
+    pub enabled: bool,
+
+    /// Whether the Drawer's sprite is displayed on the canvas.
+    pub visible: bool,
+
+    /// The position of the Drawer.
+    pub pos: Vec2,
+
+    /// The angle of the Drawer.
+    pub ang: Angle,
+
+    /// The line drawn by the drawer.
+    pub drawings: Drawings,
+
+    /// The color of the Drawer.
+    pub color: Color,
+
+    /// The color of the regions filled by the Drawer, if this is [`None`] the regions are filled with the Drawer's color.
+    pub fill_color: Option<Color>,
+
+    /// The points the Drawer has visited since `begin_fill` was called, this is [`None`] if the Drawer isn't filling.
+    pub fill_path: Option<Vec<Vec2>>,
+
+    /// The paths the Drawer is moved along are collected here instead of being traced, if this is [`Some`].
+    /// This is used to make the Drawer walk along its path gradually, see [`tasks::animate_walking_functions`].
+    #[serde(skip)]
+    pub queued_path: Option<Vec<Vec2>>,
+
+    /// The shapes filled while the Drawer's path is being queued, these are only filled once the Drawer has walked along the path.
+    #[serde(skip)]
+    pub queued_polygons: Vec<FilledPolygonPoints>,
+
+    /// The width of the lines drawn by the Drawer.
+    pub pen_size: f32,
+
+    /// The shape of the ends of the lines drawn by the Drawer.
+    pub line_cap: LineCap,
+
+    /// The shape of the corners of the lines drawn by the Drawer.
+    pub line_join: LineJoin,
+
+    /// The name of the [`ProjectImage`] the Drawer is displayed as, if this is [`None`] the Drawer is displayed as Ferris.
+    pub costume: Option<String>,
+
+    /// Identifies the state of the Drawer, this changes every time the Drawer is modified through [`Drawers::get_mut`].
+    /// The canvas only redraws the Drawer if this has changed since it was last drawn.
+    #[serde(skip, default = "next_revision")]
+    revision: u64,
+
+    /// The number of points the Drawer has added to its drawings (or removed, if negative) since it was borrowed through [`Drawers::get_mut`].
+    /// This is added to [`Drawers::point_count`] once the Drawer is released.
+    #[serde(skip)]
+    point_delta: isize,
+}
+
+/// The drawers are compared by their state, the revision and the points which haven't been counted yet are left out.
+impl PartialEq for Drawer
+{
+    fn eq(&self, other: &Self) -> bool
     {
-        &mut self.0
+        self.enabled == other.enabled
+            && self.visible == other.visible
+            && self.pos == other.pos
+            && self.ang == other.ang
+            && self.drawings == other.drawings
+            && self.color == other.color
+            && self.fill_color == other.fill_color
+            && self.fill_path == other.fill_path
+            && self.queued_path == other.queued_path
+            && self.queued_polygons == other.queued_polygons
+            && self.pen_size == other.pen_size
+            && self.line_cap == other.line_cap
+            && self.line_join == other.line_join
+            && self.costume == other.costume
     }
 }
 
-/// Create a valid* [`Lua`] runtime.
-/// This function automaticly adds all the functions to the global variables.
-#[cfg(not(target_family = "wasm"))]
-pub fn init_lua_functions(
-    lua_rt: ResMut<LuaRuntime>,
-    draw_requester: Res<DrawRequester>,
-    drawers_handle: Drawers,
-    output_list: Arc<RwLock<SetLenBuffer<ScriptLinePrompts>>>,
-    demo_buffer: DemoBuffer<Vec<DemoStep>>,
-    toast_handle: Arc<Mutex<Toasts>>,
-)
+impl Drawer
 {
-    let lua_vm = lua_rt.clone();
-    let demo_buffer_handle = demo_buffer.clone();
+    /// Returns the revision of the Drawer, which changes every time the Drawer is modified.
+    pub fn revision(&self) -> u64
+    {
+        self.revision
+    }
 
-    let print = lua_vm
-        .create_function(move |_, msg: String| {
-            if let Some(buffer) = demo_buffer_handle.get_state_if_eq(DemoBufferState::Record) {
-                buffer.write().push(DemoStep::Print(msg));
+    /// Gives the Drawer a new revision, this must be called every time the Drawer is modified, unless it was accessed through [`Drawers::get_mut`].
+    pub fn mark_changed(&mut self)
+    {
+        self.revision = next_revision();
+    }
 
-                return Ok(());
+    /// Creates a [`LinePoint`] at the given position, with the Drawer's current color and pen size.
+    pub fn line_point(&self, pos: Vec2) -> LinePoint
+    {
+        (Vec3::new(pos.x, pos.y, 0.), self.color, self.pen_size)
+    }
+
+    /// Returns the color the Drawer fills regions and shapes with.
+    pub fn current_fill_color(&self) -> Color
+    {
+        self.fill_color.unwrap_or(self.color)
+    }
+
+    /// Starts a new [`LineStrip`] from the Drawer's current position, with the Drawer's current line cap and join.
+    pub fn begin_line_strip(&mut self)
+    {
+        let point = self.line_point(self.pos);
+
+        self.drawings
+            .lines
+            .push(LineStrip::new(vec![point], self.line_cap, self.line_join));
+
+        self.point_delta += 1;
+    }
+
+    /// Adds the point to the end of the current [`LineStrip`], without moving the Drawer.
+    pub fn push_line_point(&mut self, point: LinePoint)
+    {
+        self.drawings.lines.last_mut().unwrap().extend([point]);
+
+        self.point_delta += 1;
+    }
+
+    /// Adds a filled polygon to the Drawer's drawings.
+    pub fn push_polygon(&mut self, polygon: FilledPolygonPoints)
+    {
+        self.point_delta += polygon.point_count() as isize;
+
+        self.drawings.polygons.push(polygon);
+    }
+
+    /// Adds a text to the Drawer's drawings, the text counts as one point.
+    pub fn push_text(&mut self, label: TextLabel)
+    {
+        self.point_delta += 1;
+
+        self.drawings.texts.push(label);
+    }
+
+    /// Adds a stamp to the Drawer's drawings, the stamp counts as one point.
+    pub fn push_stamp(&mut self, stamp: Stamp)
+    {
+        self.point_delta += 1;
+
+        self.drawings.stamps.push(stamp);
+    }
+
+    /// Returns the number of points the lines and the polygons of the Drawer contain, every text and stamp counts as one point.
+    pub fn point_count(&self) -> usize
+    {
+        let line_points: usize = self.drawings.lines.iter().map(|line| line.points.len()).sum();
+
+        let polygon_points: usize = self
+            .drawings
+            .polygons
+            .iter()
+            .map(FilledPolygonPoints::point_count)
+            .sum();
+
+        line_points + polygon_points + self.drawings.texts.len() + self.drawings.stamps.len()
+    }
+
+    /// Moves the Drawer through the points of the path, the Drawer will end up at the last point.
+    /// The path is added to the current [`LineStrip`] if the Drawer is enabled, and to the fill path if the Drawer is filling.
+    /// If the Drawer's path is being queued the path is only added to the queue, and the Drawer is left in place.
+    pub fn trace_path(&mut self, path: &[Vec2])
+    {
+        let Some(last_point) = path.last()
+        else {
+            return;
+        };
+
+        if let Some(queued_path) = &mut self.queued_path {
+            queued_path.extend_from_slice(path);
+
+            return;
+        }
+
+        if let Some(fill_path) = &mut self.fill_path {
+            fill_path.extend_from_slice(path);
+        }
+
+        if self.enabled {
+            let points: Vec<LinePoint> = path.iter().map(|point| self.line_point(*point)).collect();
+
+            self.point_delta += points.len() as isize;
+
+            self.drawings.lines.last_mut().unwrap().extend(points);
+        }
+
+        self.pos = *last_point;
+    }
+
+    /// Removes the point from the end of the current [`LineStrip`] and the fill path, if the Drawer is at the point.
+    /// The walking drawers trace the point they have walked to in the middle of a line, and remove it once they walk further.
+    pub fn untrace_point(&mut self, point: Vec2)
+    {
+        if self.pos != point {
+            return;
+        }
+
+        if let Some(fill_path) = &mut self.fill_path {
+            if fill_path.len() > 1 && fill_path.last() == Some(&point) {
+                fill_path.pop();
             }
+        }
 
-            output_list.write().push(ScriptLinePrompts::Standard(msg));
+        if let Some(line) = self.drawings.lines.last_mut() {
+            if line
+                .points
+                .last()
+                .is_some_and(|(position, ..)| position.truncate() == point)
+            {
+                line.pop();
 
-            Ok(())
-        })
-        .unwrap();
+                self.point_delta -= 1;
+            }
+        }
+    }
 
-    let drawers_clone = drawers_handle.clone();
-    let demo_buffer_handle = demo_buffer.clone();
+    /// Starts collecting the points the Drawer visits, the points collected earlier are discarded.
+    pub fn begin_fill(&mut self)
+    {
+        self.fill_path = Some(vec![self.pos]);
+    }
 
-    // Creates a new drawer with the Drawer handle, from a unique handle.
-    let new = lua_vm
-        .create_function(move |_, id: String| {
-            if !drawers_clone.contains_key(&id) {
-                if let Some(buffer) = demo_buffer_handle.get_state_if_eq(DemoBufferState::Record) {
-                    buffer.write().push(DemoStep::New(id.clone()));
-                }
+    /// Fills the polygon made up of the points the Drawer has visited since [`Drawer::begin_fill`] was called.
+    /// Nothing is filled if the Drawer wasn't filling or it hasn't visited enough points to make up a polygon.
+    pub fn end_fill(&mut self)
+    {
+        let Some(fill_path) = self.fill_path.take()
+        else {
+            return;
+        };
+
+        if fill_path.len() < 3 {
+            return;
+        }
+
+        self.push_polygon(FilledPolygonPoints::new(
+            fill_path.iter().map(|point| point.extend(0.)).collect(),
+            self.current_fill_color(),
+        ));
+    }
+
+    /// Turns the Drawer in the given direction, the Drawer's angle is left as is if the direction is zero.
+    pub fn face_direction(&mut self, direction: Vec2)
+    {
+        if direction != Vec2::ZERO {
+            self.ang = Angle::from_degrees(direction.to_angle().to_degrees());
+        }
+    }
+
+    /// Moves the Drawer along the curves one after the other, the curves are flattened into lines with [`CubicBezier::flatten`].
+    /// The Drawer is turned in the direction the last curve ends in.
+    pub fn trace_curves(&mut self, curves: &[CubicBezier])
+    {
+        let Some(last_curve) = curves.last()
+        else {
+            return;
+        };
+
+        let path: Vec<Vec2> = curves.iter().flat_map(|curve| curve.flatten()).collect();
+
+        self.trace_path(&path);
+
+        self.face_direction(last_curve.end_direction());
+    }
+
+    /// Fills a shape the Drawer has travelled around, the shape is queued if the Drawer's path is being queued.
+    fn fill_shape(&mut self, polygon: FilledPolygonPoints)
+    {
+        if self.queued_path.is_some() {
+            self.queued_polygons.push(polygon);
+        }
+        else {
+            self.push_polygon(polygon);
+        }
+    }
+
+    /// Moves the Drawer along a circular arc, starting in the direction the Drawer is heading.
+    /// If the radius is positive the center of the arc is on the left side of the Drawer and it turns left, otherwise it turns right.
+    /// The Drawer's angle is turned by the amount of degrees it has travelled around the center.
+    /// If `filled` is true the sector of the arc is filled, or the whole circle if it was travelled around fully.
+    pub fn arc(&mut self, radius: f32, degrees: f32, filled: bool)
+    {
+        if radius == 0. || degrees == 0. {
+            return;
+        }
+
+        let heading = Vec2::from_angle(self.ang.to_radians());
+
+        // The center is on the left side if the radius is positive.
+        let center = self.pos + heading.perp() * radius;
+        let x_axis = self.pos - center;
 
-                drawers_clone.insert(id.clone(), Drawer::default());
+        // The arc is travelled clockwise if the radius is negative.
+        let sweep = degrees.to_radians() * radius.signum();
+
+        let mut path = geometry::elliptic_arc_points(center, x_axis, x_axis.perp(), sweep);
+
+        // Make sure that full circles end exactly where they have started, so that their line is closed.
+        if degrees % 360. == 0. {
+            *path.last_mut().unwrap() = self.pos;
+        }
+
+        if filled {
+            let mut polygon_points = vec![];
+
+            // Only include the center if we are not filling a whole circle.
+            if degrees.abs() < 360. {
+                polygon_points.push(center.extend(0.));
             }
-            else {
-                return Err(mlua::Error::RuntimeError(format!(
-                    r#"The drawer with handle "{id}" already exists."#
-                )));
+
+            polygon_points.push(self.pos.extend(0.));
+            polygon_points.extend(path.iter().map(|point| point.extend(0.)));
+
+            self.fill_shape(FilledPolygonPoints::new(polygon_points, self.current_fill_color()));
+        }
+
+        self.trace_path(&path);
+
+        self.ang = Angle::from_degrees(self.ang.to_degrees() + sweep.to_degrees());
+    }
+
+    /// Draws an ellipse which starts and ends at the Drawer's position, in the direction the Drawer is heading.
+    /// `radius_x` is the radius along the Drawer's heading, `radius_y` is the radius towards the Drawer's left side.
+    /// The Drawer ends up at the same position and angle as it has started from.
+    /// If `filled` is true the ellipse is filled.
+    pub fn ellipse(&mut self, radius_x: f32, radius_y: f32, filled: bool)
+    {
+        if radius_x == 0. || radius_y == 0. {
+            return;
+        }
+
+        let heading = Vec2::from_angle(self.ang.to_radians());
+        let left = heading.perp();
+
+        let center = self.pos + left * radius_y;
+
+        let mut path = geometry::elliptic_arc_points(
+            center,
+            -left * radius_y,
+            heading * radius_x,
+            2. * std::f32::consts::PI,
+        );
+
+        // Make sure that the ellipse ends exactly where it has started, so that its line is closed.
+        *path.last_mut().unwrap() = self.pos;
+
+        if filled {
+            self.fill_shape(FilledPolygonPoints::new(
+                path.iter().map(|point| point.extend(0.)).collect(),
+                self.current_fill_color(),
+            ));
+        }
+
+        self.trace_path(&path);
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Drawings
+{
+    /// The lines drawn by the drawer.
+    pub lines: Vec<LineStrip>,
+    /// The polygons drawn by the drawer.
+    pub polygons: Vec<FilledPolygonPoints>,
+    /// The texts written by the drawer.
+    pub texts: Vec<TextLabel>,
+    /// The copies of the drawer's sprite left on the canvas.
+    pub stamps: Vec<Stamp>,
+}
+
+impl Default for Drawings
+{
+    fn default() -> Self
+    {
+        Self {
+            lines: vec![LineStrip::new(
+                vec![(Vec3::default(), Color::WHITE, DEFAULT_PEN_SIZE)],
+                LineCap::default(),
+                LineJoin::default(),
+            )],
+            polygons: vec![],
+            texts: vec![],
+            stamps: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum DrawingType
+{
+    Line(LineStrip),
+    Polygon(FilledPolygonPoints),
+    Text(TextLabel),
+    Stamp(Stamp),
+}
+
+impl Default for Drawer
+{
+    fn default() -> Self
+    {
+        Self {
+            enabled: true,
+            visible: true,
+            pos: Vec2::default(),
+            ang: Angle::from_degrees(90.),
+            drawings: Drawings::default(),
+            color: Color::WHITE,
+            fill_color: None,
+            fill_path: None,
+            queued_path: None,
+            queued_polygons: vec![],
+            pen_size: DEFAULT_PEN_SIZE,
+            line_cap: LineCap::default(),
+            line_join: LineJoin::default(),
+            costume: None,
+            revision: next_revision(),
+            point_delta: 0,
+        }
+    }
+}
+
+/// The revision the next modified [`Drawer`] or drawing gets, the revisions are unique so that nothing ever gets an earlier revision back.
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(0);
+
+/// Returns a revision no [`Drawer`] or drawing has had before.
+fn next_revision() -> u64
+{
+    NEXT_REVISION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The list of the drawers currently alive.
+/// This list is modified through the [`Lua`] runtime.
+/// The key is a [`String`] is used to identify each individual [`Drawer`].
+#[derive(Resource, Default, Debug, Clone)]
+pub struct Drawers(
+    Arc<DashMap<String, Drawer>>,
+    /// The number of points the drawings of all the drawers contain, see [`Drawers::point_count`].
+    Arc<AtomicUsize>,
+);
+
+/// A copy of every drawer at a given moment, see [`Drawers::snapshot`].
+pub type DrawersSnapshot = HashMap<String, Drawer>;
+
+/// A [`Drawer`] borrowed mutably through [`Drawers::get_mut`].
+/// The points the Drawer has added to its drawings are counted once it is released.
+pub struct DrawerMut<'a>
+{
+    drawer: dashmap::mapref::one::RefMut<'a, String, Drawer>,
+    point_count: &'a AtomicUsize,
+}
+
+impl Deref for DrawerMut<'_>
+{
+    type Target = Drawer;
+
+    fn deref(&self) -> &Self::Target
+    {
+        &self.drawer
+    }
+}
+
+impl DerefMut for DrawerMut<'_>
+{
+    fn deref_mut(&mut self) -> &mut Self::Target
+    {
+        &mut self.drawer
+    }
+}
+
+impl Drop for DrawerMut<'_>
+{
+    fn drop(&mut self)
+    {
+        let point_delta = std::mem::take(&mut self.drawer.point_delta);
+
+        if point_delta >= 0 {
+            self.point_count
+                .fetch_add(point_delta as usize, Ordering::Relaxed);
+        }
+        else {
+            self.point_count
+                .fetch_sub(point_delta.unsigned_abs(), Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drawers
+{
+    /// Creates an empty list of drawers.
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /// Returns the Drawer with the given handle.
+    pub fn get(&self, id: &str) -> Option<dashmap::mapref::one::Ref<'_, String, Drawer>>
+    {
+        self.0.get(id)
+    }
+
+    /// Returns whether a Drawer has the given handle.
+    pub fn contains_key(&self, id: &str) -> bool
+    {
+        self.0.contains_key(id)
+    }
+
+    /// Iterates over the drawers, the drawers can only be read.
+    pub fn iter(&self) -> dashmap::iter::Iter<'_, String, Drawer>
+    {
+        self.0.iter()
+    }
+
+    /// Returns the number of drawers.
+    pub fn len(&self) -> usize
+    {
+        self.0.len()
+    }
+
+    /// Returns whether there aren't any drawers.
+    pub fn is_empty(&self) -> bool
+    {
+        self.0.is_empty()
+    }
+
+    /// Returns the Drawer with the given handle mutably, and marks it as changed so that it is redrawn.
+    /// The drawers can only be modified through the methods of [`Drawers`], so that no modification can be missed by the canvas or by [`Drawers::point_count`].
+    pub fn get_mut(&self, id: &str) -> Option<DrawerMut<'_>>
+    {
+        let mut drawer = self.0.get_mut(id)?;
+
+        drawer.mark_changed();
+
+        Some(DrawerMut {
+            drawer,
+            point_count: &self.1,
+        })
+    }
+
+    /// Adds the Drawer with the given handle, replacing the Drawer which had the same handle.
+    pub fn insert(&self, id: String, mut drawer: Drawer) -> Option<Drawer>
+    {
+        drawer.point_delta = 0;
+
+        self.1.fetch_add(drawer.point_count(), Ordering::Relaxed);
+
+        let replaced = self.0.insert(id, drawer);
+
+        if let Some(replaced) = &replaced {
+            self.1.fetch_sub(replaced.point_count(), Ordering::Relaxed);
+        }
+
+        replaced
+    }
+
+    /// Removes the Drawer with the given handle.
+    pub fn remove(&self, id: &str) -> Option<(String, Drawer)>
+    {
+        let removed = self.0.remove(id);
+
+        if let Some((_, drawer)) = &removed {
+            self.1.fetch_sub(drawer.point_count(), Ordering::Relaxed);
+        }
+
+        removed
+    }
+
+    /// Removes every drawer.
+    pub fn clear(&self)
+    {
+        self.0.clear();
+        self.1.store(0, Ordering::Relaxed);
+    }
+
+    /// Wipes the drawings of every drawer, the drawers start new lines from where they are.
+    pub fn wipe(&self)
+    {
+        let mut point_count = 0;
+
+        for mut drawer in self.0.iter_mut() {
+            let drawer = drawer.value_mut();
+
+            drawer.drawings = Drawings::default();
+            drawer.begin_line_strip();
+            drawer.mark_changed();
+
+            drawer.point_delta = 0;
+            point_count += drawer.point_count();
+        }
+
+        self.1.store(point_count, Ordering::Relaxed);
+    }
+
+    /// Copies every drawer, so that they can be restored later with [`Drawers::restore`].
+    pub fn snapshot(&self) -> DrawersSnapshot
+    {
+        self.iter()
+            .map(|drawer| (drawer.key().clone(), drawer.value().clone()))
+            .collect()
+    }
+
+    /// Replaces every drawer with the drawers of the snapshot.
+    pub fn restore(&self, snapshot: DrawersSnapshot)
+    {
+        self.clear();
+
+        for (id, drawer) in snapshot {
+            self.insert(id, drawer);
+        }
+    }
+
+    /// Returns the number of points the lines and the polygons of all the drawers contain, every text and stamp counts as one point.
+    /// The points are counted as they are drawn, so this doesn't have to go through the drawings.
+    pub fn point_count(&self) -> usize
+    {
+        self.1.load(Ordering::Relaxed)
+    }
+
+    /// Creates a polygon filling the smallest region around the position, which is enclosed by the lines of all the drawers.
+    /// Returns [`None`] if the position is not enclosed by any of the lines.
+    pub fn fill_region(&self, position: Vec2, color: Color) -> Option<FilledPolygonPoints>
+    {
+        let lines: Vec<Line> = self
+            .iter()
+            .flat_map(|drawer| {
+                drawer
+                    .drawings
+                    .lines
+                    .iter()
+                    .flat_map(|line_strip| {
+                        line_strip
+                            .points
+                            .windows(2)
+                            .map(|points| Line::new(points[0].0, points[1].0))
+                            .collect::<Vec<Line>>()
+                    })
+                    .collect::<Vec<Line>>()
+            })
+            .collect();
+
+        let (outline, holes) = geometry::enclosing_region(&lines, position)?;
+
+        Some(
+            FilledPolygonPoints::new(
+                outline.iter().map(|point| point.extend(0.)).collect(),
+                color,
+            )
+            .with_holes(
+                holes
+                    .iter()
+                    .map(|hole| hole.iter().map(|point| point.extend(0.)).collect())
+                    .collect(),
+            ),
+        )
+    }
+}
+
+/// The parts of the project the lua functions share with the app, these are stored in the [`ui::UiState`] and saved with the project.
+#[cfg(not(target_family = "wasm"))]
+#[derive(Clone)]
+pub struct ProjectHandles
+{
+    /// The images the drawers can use as their costumes.
+    pub image_library: Arc<RwLock<ImageLibrary>>,
+    /// The named colors defined by the scripts.
+    pub palette: Arc<RwLock<Palette>>,
+    /// How fast the drawers walk along the lines they draw.
+    pub animation_speed: Arc<RwLock<AnimationSpeed>>,
+    /// The limits of how long the scripts can run at once.
+    pub execution_limits: Arc<RwLock<ExecutionLimits>>,
+}
+
+/// Create a valid* [`Lua`] runtime.
+/// This function automaticly adds all the functions to the global variables.
+#[cfg(not(target_family = "wasm"))]
+pub fn init_lua_functions(
+    lua_rt: ResMut<LuaRuntime>,
+    drawers_handle: Drawers,
+    output_list: Arc<RwLock<SetLenBuffer<ScriptLinePrompts>>>,
+    demo_buffer: DemoBuffer<Vec<DemoStep>>,
+    toast_handle: Arc<Mutex<Toasts>>,
+    project: ProjectHandles,
+    script_tasks: tasks::ScriptTasks,
+)
+{
+    let ProjectHandles {
+        image_library,
+        palette,
+        animation_speed,
+        execution_limits,
+    } = project;
+
+    let lua_vm = lua_rt.clone();
+
+    tasks::limit_execution(
+        &lua_vm,
+        drawers_handle.clone(),
+        toast_handle.clone(),
+        execution_limits.clone(),
+    );
+
+    let point_limit = tasks::PointLimit::new(
+        drawers_handle.clone(),
+        toast_handle.clone(),
+        execution_limits.clone(),
+    );
+    let demo_buffer_handle = demo_buffer.clone();
+
+    let print = lua_vm
+        .create_function(move |_, msg: String| {
+            if let Some(buffer) = demo_buffer_handle.get_state_if_eq(DemoBufferState::Record) {
+                buffer.write().push(DemoStep::Print(msg));
+
+                return Ok(());
+            }
+
+            output_list.write().push(ScriptLinePrompts::Standard(msg));
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+    let toasts_handle = toast_handle.clone();
+
+    // Creates a new drawer with the Drawer handle, from a unique handle.
+    let new = lua_vm
+        .create_function(move |_, id: String| {
+            if drawers_clone.contains_key(&id) {
+                return Err(mlua::Error::RuntimeError(format!(
+                    r#"The drawer with handle "{id}" already exists."#
+                )));
+            }
+
+            // A recording is limited when it's played, as its steps are run through these functions again.
+            if let Some(buffer) = demo_buffer_handle.get_state_if_eq(DemoBufferState::Record) {
+                buffer.write().push(DemoStep::New(id.clone()));
+            }
+            else {
+                let max_drawers = execution_limits.read().max_drawers;
+
+                if max_drawers != 0 && drawers_clone.len() >= max_drawers {
+                    let message = format!(
+                        "There can't be more than {max_drawers} drawers at once. Remove some of the drawers, or raise the limit in the settings."
+                    );
+
+                    toasts_handle.lock().add(
+                        Toast::new()
+                            .kind(egui_toast::ToastKind::Warning)
+                            .text(message.clone()),
+                    );
+
+                    return Err(mlua::Error::RuntimeError(message));
+                }
+            }
+
+            drawers_clone.insert(id, Drawer::default());
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+
+    // Sets the drawer's angle.
+    let rotate_drawer = lua_vm
+        .create_function(move |_, params: (String, f32)| {
+            // Get params
+            let (id, degrees) = params;
+
+            // Clone the drawers' list handle
+            let drawer_handle = drawers_clone.get_mut(&id);
+
+            match drawer_handle {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::Rotate(
+                            id,
+                            NonNaN::<f32>::new(degrees).unwrap_or_default(),
+                        ));
+
+                        return Ok(());
+                    }
+
+                    // Set the drawer's angle.
+                    drawer.ang = Angle::from_degrees(drawer.ang.to_degrees() + degrees);
+                },
+                None => {
+                    // Return the error
+                    return Err(mlua::Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+        let drawers_clone = drawers_handle.clone();
+        let demo_buffer_handle = demo_buffer.clone();
+        // Sets the drawer's angle.
+        
+        let set_drawer_angle = lua_vm
+        .create_function(move |_, params: (String, f32)| {
+            // Get params
+            let (id, degrees) = params;
+
+            // Clone the drawers' list handle
+            let drawer_handle = drawers_clone.get_mut(&id);
+
+            match drawer_handle {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::SetAngle(
+                            id,
+                            NonNaN::<f32>::new(degrees).unwrap_or_default(),
+                        ));
+
+                        return Ok(());
+                    }
+
+                    // Set the drawer's angle.
+                    drawer.ang = Angle::from_degrees(degrees);
+                },
+                None => {
+                    // Return the error
+                    return Err(mlua::Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+
+    // Resets the drawers position and angle.
+    let center = lua_vm
+        .create_function(move |_, id: String| {
+            // Fetich the drawer's handle.
+            let drawer_handle = drawers_clone.get_mut(&id);
+
+            match drawer_handle {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::Center(id));
+
+                        return Ok(());
+                    }
+
+                    //Reset the drawer's position.
+                    drawer.pos = Vec2::default();
+
+                    // The center is visited by the drawer, even though no line is drawn to it.
+                    if let Some(fill_path) = &mut drawer.fill_path {
+                        fill_path.push(Vec2::default());
+                    }
+
+                    //Add the reseted pos to the drawer
+                    drawer.begin_line_strip();
+
+                    //Reset the drawer's angle.
+                    drawer.ang = Angle::from_degrees(90.);
+                },
+                None => {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+    let palette_handle = palette.clone();
+
+    // Sets the color of the drawing
+    let color = lua_vm
+        .create_function(move |_, params: (String, Variadic<mlua::Value>)| {
+            // Get params
+            let (id, color_args) = params;
+
+            let color = color_from_lua_args(&palette_handle.read(), &color_args)?;
+
+            // Fetich the drawer's handle.
+            let drawer_handle = drawers_clone.get_mut(&id);
+
+            match drawer_handle {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        // The color is stored resolved, so that the demo doesn't depend on the project's palette.
+                        let [red, green, blue, alpha] = color_into_srgba(color);
+
+                        buffer.write().push(DemoStep::Color(
+                            id,
+                            NonNaN::<f32>::new(red).unwrap_or_default(),
+                            NonNaN::<f32>::new(green).unwrap_or_default(),
+                            NonNaN::<f32>::new(blue).unwrap_or_default(),
+                            NonNaN::<f32>::new(alpha).unwrap_or_default(),
+                        ));
+
+                        return Ok(());
+                    }
+
+                    // Set the drawer's color
+                    drawer.color = color;
+                },
+                None => {
+                    // Return the error
+                    return Err(mlua::Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+    // Creates a color from its hue, saturation, value and alpha, in the format `color` accepts.
+    let hsv = lua_vm
+        .create_function(move |_, params: (f32, f32, f32, Option<f32>)| {
+            let (hue, saturation, value, alpha) = params;
+
+            Ok(color_into_srgba(hsv_color(
+                hue,
+                saturation,
+                value,
+                alpha.unwrap_or(1.),
+            )))
+        })
+        .unwrap();
+
+    let palette_handle = palette.clone();
+
+    // Adds a named color to the project's palette, which can be used by its name afterwards.
+    let define_color = lua_vm
+        .create_function(move |_, params: (String, Variadic<mlua::Value>)| {
+            let (name, color_args) = params;
+
+            if name.trim().is_empty() || name.trim_start().starts_with('#') {
+                return Err(Error::RuntimeError(format!(
+                    r##"Invalid color name "{name}", the name must not be empty or start with "#"."##
+                )));
+            }
+
+            let color = color_from_lua_args(&palette_handle.read(), &color_args)?;
+
+            palette_handle.write().insert(name.trim(), color);
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+    let point_limit_clone = point_limit.clone();
+
+    // Moves the drawer forward by a set amount of units, this makes the drawer draw too.
+    let forward = lua_vm
+        .create_function(move |_, params: (String, f32)| {
+            point_limit_clone.check()?;
+
+            // Get params
+            let (id, amount) = params;
+
+            // Fetich the drawer's handle.
+            let drawer_handle = drawers_clone.get_mut(&id);
+
+            match drawer_handle {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::Forward(
+                            id,
+                            NonNaN::<f32>::new(amount).unwrap_or_default(),
+                        ));
+
+                        return Ok(());
+                    }
+
+                    // Calculate the difference on the y and x coordinate from its angle.
+                    // Get origin
+                    let origin = drawer.pos;
+
+                    // Degrees into radians.
+                    let angle_rad = drawer.ang.to_radians();
+
+                    // Forward units
+                    let amount_forward = amount;
+
+                    // The new x.
+                    let x = origin.x
+                        + (amount_forward * floating_point_calculation_error(angle_rad.cos()));
+                    // The new y.
+                    let y = origin.y
+                        + (amount_forward * floating_point_calculation_error(angle_rad.sin()));
+
+                    //Move the drawer to the new position, this stores the position with the drawer's color and pen size if it is enabled
+                    drawer.trace_path(&[Vec2::new(x, y)]);
+                },
+                None => {
+                    //Reset the drawer's position
+                    return Err(mlua::Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+
+    let wipe = lua_vm
+        .create_function(move |_, _: ()| {
+            if let Some(buffer) = demo_buffer_handle.get_state_if_eq(DemoBufferState::Record) {
+                buffer.write().push(DemoStep::Wipe);
+
+                return Ok(());
+            }
+
+            drawers_clone.wipe();
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+
+    let exists = lua_vm
+        .create_function(move |_, id: String| Ok(drawers_clone.contains_key(&id)))
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+
+    let remove = lua_vm
+        .create_function(move |_, id: String| {
+            if drawers_clone.remove(&id).is_none() {
+                return Err(Error::RuntimeError(format!(
+                    r#"The drawer with handle "{id}" doesn't exist."#
+                )));
+            }
+
+            if let Some(buffer) = demo_buffer_handle.get_state_if_eq(DemoBufferState::Record) {
+                buffer.write().push(DemoStep::Remove(id));
+
+                return Ok(());
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+
+    let drawers = lua_vm
+        .create_function(move |_, _: ()| {
+            let mut names = Vec::new();
+
+            for drawer in drawers_clone.iter() {
+                names.push(drawer.key().clone());
+            }
+
+            Ok(names)
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+
+    let enable = lua_vm
+        .create_function(move |_, id: String| {
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::Enable(id));
+
+                        return Ok(());
+                    }
+
+                    drawer.enabled = true;
+
+                    drawer.begin_line_strip();
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+
+    let disable = lua_vm
+        .create_function(move |_, id: String| {
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::Disable(id));
+
+                        return Ok(());
+                    }
+
+                    drawer.enabled = false;
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+    let point_limit_clone = point_limit.clone();
+
+    // Fills the smallest region enclosed by the lines around the drawer.
+    let fill = lua_vm
+        .create_function(move |_, id: String| {
+            point_limit_clone.check()?;
+
+            // The drawer's handle is released before the lines of every drawer are read.
+            let drawer_info = drawers_clone
+                .get(&id)
+                .map(|drawer| (drawer.pos, drawer.current_fill_color()));
+
+            match drawer_info {
+                Some((position, fill_color)) => {
+                    if let Some(buffer) = demo_buffer_handle.get_state_if_eq(DemoBufferState::Record) {
+                        buffer.write().push(DemoStep::Fill(id));
+
+                        return Ok(());
+                    }
+
+                    if let Some(polygon) = drawers_clone.fill_region(position, fill_color) {
+                        if let Some(mut drawer) = drawers_clone.get_mut(&id) {
+                            drawer.push_polygon(polygon);
+                        }
+                    }
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+
+    // Starts collecting the points the drawer visits, so that they can be filled by `end_fill`.
+    let begin_fill = lua_vm
+        .create_function(move |_, id: String| {
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::BeginFill(id));
+
+                        return Ok(());
+                    }
+
+                    drawer.begin_fill();
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+    let point_limit_clone = point_limit.clone();
+
+    // Fills the polygon made up of the points the drawer has visited since `begin_fill`.
+    let end_fill = lua_vm
+        .create_function(move |_, id: String| {
+            point_limit_clone.check()?;
+
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::EndFill(id));
+
+                        return Ok(());
+                    }
+
+                    drawer.end_fill();
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+    let palette_handle = palette.clone();
+
+    // Sets the color the drawer fills with, calling it without a color makes the drawer fill with its own color again.
+    let fill_color = lua_vm
+        .create_function(move |_, params: (String, Variadic<mlua::Value>)| {
+            let (id, color_args) = params;
+
+            let color = if color_args.is_empty() {
+                None
+            }
+            else {
+                Some(color_from_lua_args(&palette_handle.read(), &color_args)?)
+            };
+
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        // The color is stored resolved, so that the demo doesn't depend on the project's palette.
+                        let color = color.map(|color| {
+                            color_into_srgba(color)
+                                .map(|component| NonNaN::<f32>::new(component).unwrap_or_default())
+                        });
+
+                        buffer.write().push(DemoStep::FillColor(id, color));
+
+                        return Ok(());
+                    }
+
+                    drawer.fill_color = color;
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+    let toasts_handle = toast_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+
+    let notification = lua_vm
+        .create_function(move |_, params: (u32, String)| {
+            if demo_buffer_handle.get_state() == DemoBufferState::Record {
+                return Ok(());
+            }
+
+            let (notification_type, text) = params;
+
+            let toast = Toast::new();
+
+            let toast = match notification_type {
+                1 => toast.kind(egui_toast::ToastKind::Info),
+                2 => toast.kind(egui_toast::ToastKind::Success),
+                3 => toast.kind(egui_toast::ToastKind::Error),
+                4 => toast.kind(egui_toast::ToastKind::Warning),
+                _ => toast.kind(egui_toast::ToastKind::Custom(notification_type)),
+            }
+            .text(text);
+
+            toasts_handle.lock().add(toast);
+
+            Ok(())
+        })
+        .unwrap();
+
+    let demo_buffer_handle = demo_buffer.clone();
+    let script_tasks_handle = script_tasks.clone();
+
+    // Pauses the script for the given amount of seconds, the app keeps running meanwhile.
+    let wait = lua_vm
+        .create_async_function(move |_, seconds: f32| {
+            let is_recording = demo_buffer_handle.get_state() == DemoBufferState::Record;
+            let is_task = script_tasks_handle.is_polling();
+
+            async move {
+                // Demos are recorded at once, their steps are played back one by one anyway.
+                if is_recording {
+                    return Ok(());
+                }
+
+                if !is_task {
+                    return Err(Error::RuntimeError(
+                        "Scripts can only wait when they are run from the Scripts tab or the Command Panel, callbacks can't wait.".to_string(),
+                    ));
+                }
+
+                if seconds.is_nan() {
+                    return Err(Error::RuntimeError(
+                        "The amount of seconds to wait must be a number.".to_string(),
+                    ));
+                }
+
+                let end = Instant::now() + Duration::from_secs_f32(seconds.clamp(0., MAX_WAIT_SECONDS));
+
+                // The script is paused for at least one frame, so that the drawings made before waiting are displayed.
+                loop {
+                    tasks::NextFrame::default().await;
+
+                    if Instant::now() >= end {
+                        break;
+                    }
+                }
+
+                Ok(())
+            }
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+
+    let position = lua_vm
+        .create_function(move |_, id: String| {
+            match drawers_clone.get(&id) {
+                Some(drawer) => Ok([drawer.pos.x, drawer.pos.y]),
+                None => {
+                    Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )))
+                },
+            }
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+    let point_limit_clone = point_limit.clone();
+
+    let rectangle = lua_vm
+        .create_function(move |_, params: (String, f32, f32)| {
+            let (id, desired_x, desired_y) = params;
+
+            point_limit_clone.check()?;
+
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::Rectangle(
+                            id,
+                            NonNaN::<f32>::new(desired_x).unwrap_or_default(),
+                            NonNaN::<f32>::new(desired_y).unwrap_or_default(),
+                        ));
+
+                        return Ok(());
+                    }
+
+                    let current_position = drawer.pos;
+
+                    let current_color = drawer.current_fill_color();
+                    drawer.push_polygon(FilledPolygonPoints::new(
+                        vec![
+                            Vec3::new(current_position.x, current_position.y, 0.),
+                            Vec3::new(current_position.x + (desired_x), current_position.y, 0.),
+                            Vec3::new(
+                                current_position.x + (desired_x),
+                                current_position.y + (desired_y),
+                                0.,
+                            ),
+                            Vec3::new(current_position.x, current_position.y + (desired_y), 0.),
+                        ],
+                        current_color,
+                    ));
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+
+    let point_to = lua_vm
+        .create_function(move |_, params: (String, f32, f32)| {
+            let (id, point_to_x, point_to_y) = params;
+
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::Rectangle(
+                            id,
+                            NonNaN::<f32>::new(point_to_x).unwrap_or_default(),
+                            NonNaN::<f32>::new(point_to_y).unwrap_or_default(),
+                        ));
+
+                        return Ok(());
+                    }
+
+                    let drawer_pos = drawer.pos;
+                    
+                    let dy = drawer_pos.y - point_to_y;
+                    let dx = drawer_pos.x - point_to_x;
+
+                    let atan = dy.atan2(dx);
+
+                    drawer.ang = Angle::from_degrees(atan.to_degrees() - 90.);
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+
+    // Sets the width of the lines drawn by the drawer.
+    let pen_size = lua_vm
+        .create_function(move |_, params: (String, f32)| {
+            let (id, width) = params;
+
+            if !width.is_finite() || width < 0. {
+                return Err(Error::RuntimeError(format!(
+                    "The pen size must be a non-negative number, got {width}."
+                )));
+            }
+
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::PenSize(
+                            id,
+                            NonNaN::<f32>::new(width).unwrap_or_default(),
+                        ));
+
+                        return Ok(());
+                    }
+
+                    // The width is stored per point, so the lines already drawn are not affected.
+                    drawer.pen_size = width;
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+
+    // Sets the shape of the ends of the lines drawn by the drawer.
+    let line_cap = lua_vm
+        .create_function(move |_, params: (String, String)| {
+            let (id, cap) = params;
+
+            let cap = LineCap::from_str(&cap).map_err(|_| {
+                Error::RuntimeError(format!(
+                    r#"Invalid line cap "{cap}", expected "butt", "round" or "square"."#
+                ))
+            })?;
+
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::LineCap(id, cap));
+
+                        return Ok(());
+                    }
+
+                    drawer.line_cap = cap;
+
+                    // Caps are stored per line, so a new line has to be started.
+                    drawer.begin_line_strip();
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+
+    // Sets the shape of the corners of the lines drawn by the drawer.
+    let line_join = lua_vm
+        .create_function(move |_, params: (String, String)| {
+            let (id, join) = params;
+
+            let join = LineJoin::from_str(&join).map_err(|_| {
+                Error::RuntimeError(format!(
+                    r#"Invalid line join "{join}", expected "miter", "round" or "bevel"."#
+                ))
+            })?;
+
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::LineJoin(id, join));
+
+                        return Ok(());
+                    }
+
+                    drawer.line_join = join;
+
+                    // Joins are stored per line, so a new line has to be started.
+                    drawer.begin_line_strip();
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+    let point_limit_clone = point_limit.clone();
+
+    // Moves the drawer along an arc, the arc's sector is filled if the last argument is true.
+    let arc = lua_vm
+        .create_function(move |_, params: (String, f32, f32, Option<bool>)| {
+            let (id, radius, degrees, filled) = params;
+
+            point_limit_clone.check()?;
+
+            let filled = filled.unwrap_or_default();
+
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::Arc(
+                            id,
+                            NonNaN::<f32>::new(radius).unwrap_or_default(),
+                            NonNaN::<f32>::new(degrees).unwrap_or_default(),
+                            filled,
+                        ));
+
+                        return Ok(());
+                    }
+
+                    drawer.arc(radius, degrees, filled);
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+    let point_limit_clone = point_limit.clone();
+
+    // Draws a circle with the drawer, the circle is filled if the last argument is true.
+    let circle = lua_vm
+        .create_function(move |_, params: (String, f32, Option<bool>)| {
+            let (id, radius, filled) = params;
+
+            point_limit_clone.check()?;
+
+            let filled = filled.unwrap_or_default();
+
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::Circle(
+                            id,
+                            NonNaN::<f32>::new(radius).unwrap_or_default(),
+                            filled,
+                        ));
+
+                        return Ok(());
+                    }
+
+                    // A circle is an arc which goes all the way around.
+                    drawer.arc(radius, 360., filled);
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+    let point_limit_clone = point_limit.clone();
+
+    // Draws an ellipse with the drawer, the ellipse is filled if the last argument is true.
+    let ellipse = lua_vm
+        .create_function(move |_, params: (String, f32, f32, Option<bool>)| {
+            let (id, radius_x, radius_y, filled) = params;
+
+            point_limit_clone.check()?;
+
+            let filled = filled.unwrap_or_default();
+
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::Ellipse(
+                            id,
+                            NonNaN::<f32>::new(radius_x).unwrap_or_default(),
+                            NonNaN::<f32>::new(radius_y).unwrap_or_default(),
+                            filled,
+                        ));
+
+                        return Ok(());
+                    }
+
+                    drawer.ellipse(radius_x, radius_y, filled);
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+    let point_limit_clone = point_limit.clone();
+
+    // Moves the drawer along a quadratic curve to the given position.
+    let curve_to = lua_vm
+        .create_function(move |_, params: (String, f32, f32, f32, f32)| {
+            let (id, control_x, control_y, x, y) = params;
+
+            point_limit_clone.check()?;
+
+            check_finite_coordinates(&[control_x, control_y, x, y])?;
+
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::CurveTo(
+                            id,
+                            NonNaN::<f32>::new(control_x).unwrap_or_default(),
+                            NonNaN::<f32>::new(control_y).unwrap_or_default(),
+                            NonNaN::<f32>::new(x).unwrap_or_default(),
+                            NonNaN::<f32>::new(y).unwrap_or_default(),
+                        ));
+
+                        return Ok(());
+                    }
+
+                    let curve = CubicBezier::from_quadratic(
+                        drawer.pos,
+                        Vec2::new(control_x, control_y),
+                        Vec2::new(x, y),
+                    );
+
+                    drawer.trace_curves(&[curve]);
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+    let point_limit_clone = point_limit.clone();
+
+    // Moves the drawer along a cubic curve to the given position.
+    let cubic_to = lua_vm
+        .create_function(move |_, params: (String, f32, f32, f32, f32, f32, f32)| {
+            let (id, first_x, first_y, second_x, second_y, x, y) = params;
+
+            point_limit_clone.check()?;
+
+            check_finite_coordinates(&[first_x, first_y, second_x, second_y, x, y])?;
+
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::CubicTo(
+                            id,
+                            NonNaN::<f32>::new(first_x).unwrap_or_default(),
+                            NonNaN::<f32>::new(first_y).unwrap_or_default(),
+                            NonNaN::<f32>::new(second_x).unwrap_or_default(),
+                            NonNaN::<f32>::new(second_y).unwrap_or_default(),
+                            NonNaN::<f32>::new(x).unwrap_or_default(),
+                            NonNaN::<f32>::new(y).unwrap_or_default(),
+                        ));
+
+                        return Ok(());
+                    }
+
+                    let curve = CubicBezier::new(
+                        drawer.pos,
+                        Vec2::new(first_x, first_y),
+                        Vec2::new(second_x, second_y),
+                        Vec2::new(x, y),
+                    );
+
+                    drawer.trace_curves(&[curve]);
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
+
+            Ok(())
+        })
+        .unwrap();
+
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+    let point_limit_clone = point_limit.clone();
+
+    // Moves the drawer along a smooth curve, which goes through all of the points in the list.
+    let spline = lua_vm
+        .create_function(move |_, params: (String, Vec<[f32; 2]>)| {
+            let (id, points) = params;
+
+            point_limit_clone.check()?;
+
+            check_finite_coordinates(points.as_flattened())?;
+
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::Spline(
+                            id,
+                            points
+                                .iter()
+                                .map(|[x, y]| {
+                                    (
+                                        NonNaN::<f32>::new(*x).unwrap_or_default(),
+                                        NonNaN::<f32>::new(*y).unwrap_or_default(),
+                                    )
+                                })
+                                .collect(),
+                        ));
+
+                        return Ok(());
+                    }
+
+                    // The spline starts from the drawer's current position.
+                    let spline_points: Vec<Vec2> = [drawer.pos]
+                        .into_iter()
+                        .chain(points.iter().map(|[x, y]| Vec2::new(*x, *y)))
+                        .collect();
+
+                    drawer.trace_curves(&CubicBezier::catmull_rom(&spline_points));
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
             }
 
             Ok(())
@@ -637,35 +3005,42 @@ pub fn init_lua_functions(
 
     let drawers_clone = drawers_handle.clone();
     let demo_buffer_handle = demo_buffer.clone();
+    let point_limit_clone = point_limit.clone();
 
-    // Sets the drawer's angle.
-    let rotate_drawer = lua_vm
-        .create_function(move |_, params: (String, f32)| {
-            // Get params
-            let (id, degrees) = params;
+    // Writes a text onto the canvas at the drawer's position, in the direction the drawer is heading.
+    let write = lua_vm
+        .create_function(move |_, params: (String, String, f32)| {
+            point_limit_clone.check()?;
 
-            // Clone the drawers' list handle
-            let drawer_handle = drawers_clone.get_mut(&id);
+            let (id, text, size) = params;
 
-            match drawer_handle {
+            if !size.is_finite() || size <= 0. {
+                return Err(Error::RuntimeError(format!(
+                    "The text size must be a positive number, got {size}."
+                )));
+            }
+
+            match drawers_clone.get_mut(&id) {
                 Some(mut drawer) => {
                     if let Some(buffer) =
                         demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
                     {
-                        buffer.write().push(DemoStep::Rotate(
+                        buffer.write().push(DemoStep::Write(
                             id,
-                            NonNaN::<f32>::new(degrees).unwrap_or_default(),
+                            text,
+                            NonNaN::<f32>::new(size).unwrap_or_default(),
                         ));
 
                         return Ok(());
                     }
 
-                    // Set the drawer's angle.
-                    drawer.ang = Angle::from_degrees(drawer.ang.to_degrees() + degrees);
+                    let label =
+                        TextLabel::new(text, drawer.pos, drawer.ang.clone(), size, drawer.color);
+
+                    drawer.push_text(label);
                 },
                 None => {
-                    // Return the error
-                    return Err(mlua::Error::RuntimeError(format!(
+                    return Err(Error::RuntimeError(format!(
                         r#"The drawer with handle "{id}" doesn't exist."#
                     )));
                 },
@@ -675,37 +3050,36 @@ pub fn init_lua_functions(
         })
         .unwrap();
 
-        let drawers_clone = drawers_handle.clone();
-        let demo_buffer_handle = demo_buffer.clone();
-        // Sets the drawer's angle.
-        
-        let set_drawer_angle = lua_vm
-        .create_function(move |_, params: (String, f32)| {
-            // Get params
-            let (id, degrees) = params;
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
 
-            // Clone the drawers' list handle
-            let drawer_handle = drawers_clone.get_mut(&id);
+    // Sets the image the drawer is displayed as, the drawer is displayed as Ferris if no image is given.
+    let set_costume = lua_vm
+        .create_function(move |_, params: (String, Option<String>)| {
+            let (id, costume) = params;
 
-            match drawer_handle {
+            if let Some(name) = &costume {
+                if !image_library.read().contains(name) {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The image with name "{name}" doesn't exist in the project."#
+                    )));
+                }
+            }
+
+            match drawers_clone.get_mut(&id) {
                 Some(mut drawer) => {
                     if let Some(buffer) =
                         demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
                     {
-                        buffer.write().push(DemoStep::SetAngle(
-                            id,
-                            NonNaN::<f32>::new(degrees).unwrap_or_default(),
-                        ));
+                        buffer.write().push(DemoStep::SetCostume(id, costume));
 
                         return Ok(());
                     }
 
-                    // Set the drawer's angle.
-                    drawer.ang = Angle::from_degrees(degrees);
+                    drawer.costume = costume;
                 },
                 None => {
-                    // Return the error
-                    return Err(mlua::Error::RuntimeError(format!(
+                    return Err(Error::RuntimeError(format!(
                         r#"The drawer with handle "{id}" doesn't exist."#
                     )));
                 },
@@ -717,38 +3091,29 @@ pub fn init_lua_functions(
 
     let drawers_clone = drawers_handle.clone();
     let demo_buffer_handle = demo_buffer.clone();
+    let point_limit_clone = point_limit.clone();
 
-    // Resets the drawers position and angle.
-    let center = lua_vm
+    // Leaves a copy of the drawer's sprite on the canvas.
+    let stamp = lua_vm
         .create_function(move |_, id: String| {
-            // Fetich the drawer's handle.
-            let drawer_handle = drawers_clone.get_mut(&id);
+            point_limit_clone.check()?;
 
-            match drawer_handle {
+            match drawers_clone.get_mut(&id) {
                 Some(mut drawer) => {
                     if let Some(buffer) =
                         demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
                     {
-                        buffer.write().push(DemoStep::Center(id));
+                        buffer.write().push(DemoStep::Stamp(id));
 
                         return Ok(());
                     }
 
-                    //Reset the drawer's position.
-                    drawer.pos = Vec2::default();
-
-                    let drawer_color = drawer.color;
-
-                    //Add the reseted pos to the drawer
-                    drawer.drawings.lines.push(LineStrip {
-                        points: vec![(Vec3::default(), drawer_color)],
-                    });
+                    let stamp = Stamp::new(drawer.costume.clone(), drawer.pos, drawer.ang.clone());
 
-                    //Reset the drawer's angle.
-                    drawer.ang = Angle::from_degrees(90.);
+                    drawer.push_stamp(stamp);
                 },
                 None => {
-                    return Err(mlua::Error::RuntimeError(format!(
+                    return Err(Error::RuntimeError(format!(
                         r#"The drawer with handle "{id}" doesn't exist."#
                     )));
                 },
@@ -761,37 +3126,23 @@ pub fn init_lua_functions(
     let drawers_clone = drawers_handle.clone();
     let demo_buffer_handle = demo_buffer.clone();
 
-    // Sets the color of the drawing
-    let color = lua_vm
-        .create_function(move |_, params: (String, f32, f32, f32, f32)| {
-            // Get params
-            let (id, red, green, blue, alpha) = params;
-
-            // Fetich the drawer's handle.
-            let drawer_handle = drawers_clone.get_mut(&id);
-
-            match drawer_handle {
+    // Lifts the drawer's pen, the drawer will not draw until its pen is put down.
+    let pen_up = lua_vm
+        .create_function(move |_, id: String| {
+            match drawers_clone.get_mut(&id) {
                 Some(mut drawer) => {
                     if let Some(buffer) =
                         demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
                     {
-                        buffer.write().push(DemoStep::Color(
-                            id,
-                            NonNaN::<f32>::new(red).unwrap_or_default(),
-                            NonNaN::<f32>::new(green).unwrap_or_default(),
-                            NonNaN::<f32>::new(blue).unwrap_or_default(),
-                            NonNaN::<f32>::new(alpha).unwrap_or_default(),
-                        ));
+                        buffer.write().push(DemoStep::PenUp(id));
 
                         return Ok(());
                     }
 
-                    // Set the drawer's color
-                    drawer.color = Color::linear_rgba(red, green, blue, alpha);
+                    drawer.enabled = false;
                 },
                 None => {
-                    // Return the error
-                    return Err(mlua::Error::RuntimeError(format!(
+                    return Err(Error::RuntimeError(format!(
                         r#"The drawer with handle "{id}" doesn't exist."#
                     )));
                 },
@@ -804,65 +3155,25 @@ pub fn init_lua_functions(
     let drawers_clone = drawers_handle.clone();
     let demo_buffer_handle = demo_buffer.clone();
 
-    // Moves the drawer forward by a set amount of units, this makes the drawer draw too.
-    let forward = lua_vm
-        .create_function(move |_, params: (String, f32)| {
-            // Get params
-            let (id, amount) = params;
-
-            // Fetich the drawer's handle.
-            let drawer_handle = drawers_clone.get_mut(&id);
-
-            match drawer_handle {
+    // Puts the drawer's pen down, the drawer will draw from its current position.
+    let pen_down = lua_vm
+        .create_function(move |_, id: String| {
+            match drawers_clone.get_mut(&id) {
                 Some(mut drawer) => {
                     if let Some(buffer) =
                         demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
                     {
-                        buffer.write().push(DemoStep::Forward(
-                            id,
-                            NonNaN::<f32>::new(amount).unwrap_or_default(),
-                        ));
+                        buffer.write().push(DemoStep::PenDown(id));
 
                         return Ok(());
                     }
 
-                    // Calculate the difference on the y and x coordinate from its angle.
-                    // Get origin
-                    let origin = drawer.pos;
-
-                    //Clone the color so we can move it into the lines' list.
-                    let drawer_color = drawer.color;
-
-                    // Degrees into radians.
-                    let angle_rad = drawer.ang.to_radians();
-
-                    // Forward units
-                    let amount_forward = amount;
-
-                    // The new x.
-                    let x = origin.x
-                        + (amount_forward * floating_point_calculation_error(angle_rad.cos()));
-                    // The new y.
-                    let y = origin.y
-                        + (amount_forward * floating_point_calculation_error(angle_rad.sin()));
-
-                    //Store the new position and the drawer's color if it is enabled
-                    if drawer.enabled {
-                        drawer
-                            .drawings
-                            .lines
-                            .last_mut()
-                            .unwrap()
-                            .points
-                            .push((Vec3::new(x, y, 0.), drawer_color));
-                    }
+                    drawer.enabled = true;
 
-                    //Set the new drawers position.
-                    drawer.pos = Vec2::new(x, y);
+                    drawer.begin_line_strip();
                 },
                 None => {
-                    //Reset the drawer's position
-                    return Err(mlua::Error::RuntimeError(format!(
+                    return Err(Error::RuntimeError(format!(
                         r#"The drawer with handle "{id}" doesn't exist."#
                     )));
                 },
@@ -875,49 +3186,55 @@ pub fn init_lua_functions(
     let drawers_clone = drawers_handle.clone();
     let demo_buffer_handle = demo_buffer.clone();
 
-    let wipe = lua_vm
-        .create_function(move |_, _: ()| {
-            if let Some(buffer) = demo_buffer_handle.get_state_if_eq(DemoBufferState::Record) {
-                buffer.write().push(DemoStep::Wipe);
-
-                return Ok(());
-            }
+    // Hides the drawer's sprite, the drawer still draws if its pen is down.
+    let hide = lua_vm
+        .create_function(move |_, id: String| {
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::Hide(id));
 
-            for mut drawer in drawers_clone.iter_mut() {
-                let drawer = drawer.value_mut();
+                        return Ok(());
+                    }
 
-                let mut default_drawings = Drawings::default();
-                default_drawings.lines.push(LineStrip {
-                    points: vec![(Vec3::new(drawer.pos.x, drawer.pos.y, 0.), Color::WHITE)],
-                });
-                drawer.drawings = default_drawings;
+                    drawer.visible = false;
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
             }
 
             Ok(())
         })
         .unwrap();
 
-    let drawers_clone = drawers_handle.clone();
-
-    let exists = lua_vm
-        .create_function(move |_, id: String| Ok(drawers_clone.contains_key(&id)))
-        .unwrap();
-
     let drawers_clone = drawers_handle.clone();
     let demo_buffer_handle = demo_buffer.clone();
 
-    let remove = lua_vm
+    // Displays the drawer's sprite again.
+    let show = lua_vm
         .create_function(move |_, id: String| {
-            if drawers_clone.remove(&id).is_none() {
-                return Err(Error::RuntimeError(format!(
-                    r#"The drawer with handle "{id}" doesn't exist."#
-                )));
-            }
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::Show(id));
 
-            if let Some(buffer) = demo_buffer_handle.get_state_if_eq(DemoBufferState::Record) {
-                buffer.write().push(DemoStep::Remove(id));
+                        return Ok(());
+                    }
 
-                return Ok(());
+                    drawer.visible = true;
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
             }
 
             Ok(())
@@ -925,39 +3242,32 @@ pub fn init_lua_functions(
         .unwrap();
 
     let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+    let point_limit_clone = point_limit.clone();
 
-    let drawers = lua_vm
-        .create_function(move |_, _: ()| {
-            let mut names = Vec::new();
-
-            for drawer in drawers_clone.iter() {
-                names.push(drawer.key().clone());
-            }
-
-            Ok(names)
-        })
-        .unwrap();
+    // Moves the drawer to the given position, the drawer draws a line there if its pen is down.
+    let go_to = lua_vm
+        .create_function(move |_, params: (String, f32, f32)| {
+            let (id, x, y) = params;
 
-    let drawers_clone = drawers_handle.clone();
-    let demo_buffer_handle = demo_buffer.clone();
+            check_finite_coordinates(&[x, y])?;
+            point_limit_clone.check()?;
 
-    let enable = lua_vm
-        .create_function(move |_, id: String| {
             match drawers_clone.get_mut(&id) {
                 Some(mut drawer) => {
                     if let Some(buffer) =
                         demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
                     {
-                        buffer.write().push(DemoStep::Enable(id));
+                        buffer.write().push(DemoStep::GoTo(
+                            id,
+                            NonNaN::<f32>::new(x).unwrap_or_default(),
+                            NonNaN::<f32>::new(y).unwrap_or_default(),
+                        ));
 
                         return Ok(());
                     }
 
-                    drawer.enabled = true;
-
-                    let tuple = (Vec3::new(drawer.pos.x, drawer.pos.y, 0.), drawer.color);
-
-                    drawer.drawings.lines.push(LineStrip::new(vec![tuple]));
+                    drawer.trace_path(&[Vec2::new(x, y)]);
                 },
                 None => {
                     return Err(Error::RuntimeError(format!(
@@ -969,22 +3279,35 @@ pub fn init_lua_functions(
             Ok(())
         })
         .unwrap();
+
     let drawers_clone = drawers_handle.clone();
     let demo_buffer_handle = demo_buffer.clone();
+    let point_limit_clone = point_limit.clone();
+
+    // Moves the drawer horizontally to the given x coordinate, the drawer draws a line there if its pen is down.
+    let set_x = lua_vm
+        .create_function(move |_, params: (String, f32)| {
+            let (id, x) = params;
+
+            check_finite_coordinates(&[x])?;
+            point_limit_clone.check()?;
 
-    let disable = lua_vm
-        .create_function(move |_, id: String| {
             match drawers_clone.get_mut(&id) {
                 Some(mut drawer) => {
                     if let Some(buffer) =
                         demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
                     {
-                        buffer.write().push(DemoStep::Disable(id));
+                        buffer.write().push(DemoStep::SetX(
+                            id,
+                            NonNaN::<f32>::new(x).unwrap_or_default(),
+                        ));
 
                         return Ok(());
                     }
 
-                    drawer.enabled = false;
+                    let y = drawer.pos.y;
+
+                    drawer.trace_path(&[Vec2::new(x, y)]);
                 },
                 None => {
                     return Err(Error::RuntimeError(format!(
@@ -998,68 +3321,67 @@ pub fn init_lua_functions(
         .unwrap();
 
     let drawers_clone = drawers_handle.clone();
-    let draw_request_sender = draw_requester.sender.clone();
     let demo_buffer_handle = demo_buffer.clone();
+    let point_limit_clone = point_limit.clone();
 
-    let fill = lua_vm
-        .create_function(move |_, id: String| {
-            match drawers_clone.get(&id) {
-                Some(selected_drawer) => {
-                    if let Some(buffer) = demo_buffer_handle.get_state_if_eq(DemoBufferState::Record) {
-                        buffer.write().push(DemoStep::Fill(id));
-
-                        return Ok(());
-                    }
-
-                    let drawer_lines: Vec<Vec3> = drawers_clone
-                        .iter()
-                        .flat_map(|pair| {
-                            let drawer = pair.value();
-
-                            drawer.drawings.clone().lines.iter().flat_map(|line_strip| {
-                                line_strip.points.iter().map(|points| points.0).collect::<Vec<Vec3>>()
-                            }).collect::<Vec<Vec3>>()
-                        })
-                        .collect();
+    // Moves the drawer vertically to the given y coordinate, the drawer draws a line there if its pen is down.
+    let set_y = lua_vm
+        .create_function(move |_, params: (String, f32)| {
+            let (id, y) = params;
 
-                    let mut lines: Vec<Line> = vec![];
+            check_finite_coordinates(&[y])?;
+            point_limit_clone.check()?;
 
-                    for positions in dbg!(drawer_lines).windows(2) {
-                        let (min, max) = (positions[0], positions[1]);
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::SetY(
+                            id,
+                            NonNaN::<f32>::new(y).unwrap_or_default(),
+                        ));
 
-                        lines.push(Line::new(min, max));
+                        return Ok(());
                     }
 
-                    let mut checked_lines: Vec<Line> = vec![];
+                    let x = drawer.pos.x;
 
-                    for (idx, line) in lines.iter().enumerate() {
-                        for (current_checked_idx, checked_line) in checked_lines.iter().enumerate() {
-                            if idx as isize - 1 != current_checked_idx as isize && checked_lines.len() > 2 {
-                                if let Some(intersection_pos) = line.intersects(checked_line) {
-                                    let intersected_line_idx = checked_lines.iter().position(|line| line == checked_line).unwrap();
-                                    let mut polygon_points: Vec<Coord> = vec![];
+                    drawer.trace_path(&[Vec2::new(x, y)]);
+                },
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )));
+                },
+            }
 
-                                    polygon_points.push(coord!{x: intersection_pos.x as f64, y: intersection_pos.y as f64});
+            Ok(())
+        })
+        .unwrap();
 
-                                    for poly_line in &checked_lines[intersected_line_idx + 1..idx] {
-                                        polygon_points.push(coord! {x: poly_line.max.x as f64, y: poly_line.max.y as f64});
-                                    }
+    let drawers_clone = drawers_handle.clone();
+    let demo_buffer_handle = demo_buffer.clone();
+    let point_limit_clone = point_limit.clone();
 
-                                    let polygon = Polygon::new(LineString::new(polygon_points.clone()), vec![]);
+    // Moves the drawer back to the center of the canvas and resets its angle, unlike `center` the drawer draws a line there if its pen is down.
+    let home = lua_vm
+        .create_function(move |_, id: String| {
+            point_limit_clone.check()?;
 
-                                    let poly_convex_hull = polygon.convex_hull();
+            match drawers_clone.get_mut(&id) {
+                Some(mut drawer) => {
+                    if let Some(buffer) =
+                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
+                    {
+                        buffer.write().push(DemoStep::Home(id));
 
-                                    if poly_convex_hull.contains(&point!(x: selected_drawer.pos.x as f64, y: selected_drawer.pos.y as f64)) {
-                                        draw_request_sender.send((polygon_points.iter().map(|coord| Vec3::new(coord.x as f32, coord.y as f32, 0.)).collect::<Vec<Vec3>>(), selected_drawer.color, id.clone())).unwrap();
-                                    }
+                        return Ok(());
+                    }
 
-                                    break;
-                                }
-                            }
-                        }
+                    drawer.trace_path(&[Vec2::ZERO]);
 
-                        checked_lines.push(line.clone());
-                    }
+                    drawer.ang = Angle::from_degrees(90.);
                 },
                 None => {
                     return Err(Error::RuntimeError(format!(
@@ -1072,40 +3394,45 @@ pub fn init_lua_functions(
         })
         .unwrap();
 
-    let toasts_handle = toast_handle.clone();
-    let demo_buffer_handle = demo_buffer.clone();
+    let drawers_clone = drawers_handle.clone();
 
-    let notification = lua_vm
-        .create_function(move |_, params: (u32, String)| {
-            if demo_buffer_handle.get_state() == DemoBufferState::Record {
-                return Ok(());
+    // Returns the angle of the drawer in degrees, in the same format `set_angle` uses.
+    let heading = lua_vm
+        .create_function(move |_, id: String| {
+            match drawers_clone.get(&id) {
+                Some(drawer) => Ok(drawer.ang.to_degrees()),
+                None => {
+                    Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )))
+                },
             }
+        })
+        .unwrap();
 
-            let (notification_type, text) = params;
-
-            let toast = Toast::new();
+    let drawers_clone = drawers_handle.clone();
 
-            let toast = match notification_type {
-                1 => toast.kind(egui_toast::ToastKind::Info),
-                2 => toast.kind(egui_toast::ToastKind::Success),
-                3 => toast.kind(egui_toast::ToastKind::Error),
-                4 => toast.kind(egui_toast::ToastKind::Warning),
-                _ => toast.kind(egui_toast::ToastKind::Custom(notification_type)),
+    // Returns the color of the drawer in the same format `color` uses.
+    let get_color = lua_vm
+        .create_function(move |_, id: String| {
+            match drawers_clone.get(&id) {
+                Some(drawer) => Ok(color_into_srgba(drawer.color)),
+                None => {
+                    Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )))
+                },
             }
-            .text(text);
-
-            toasts_handle.lock().add(toast);
-
-            Ok(())
         })
         .unwrap();
 
     let drawers_clone = drawers_handle.clone();
 
-    let position = lua_vm
+    // Returns whether the drawer draws when it's moved.
+    let is_enabled = lua_vm
         .create_function(move |_, id: String| {
             match drawers_clone.get(&id) {
-                Some(drawer) => Ok([drawer.pos.x, drawer.pos.y]),
+                Some(drawer) => Ok(drawer.enabled),
                 None => {
                     Err(Error::RuntimeError(format!(
                         r#"The drawer with handle "{id}" doesn't exist."#
@@ -1116,91 +3443,128 @@ pub fn init_lua_functions(
         .unwrap();
 
     let drawers_clone = drawers_handle.clone();
-    let demo_buffer_handle = demo_buffer.clone();
-
-    let rectangle = lua_vm
-        .create_function(move |_, params: (String, f32, f32)| {
-            let (id, desired_x, desired_y) = params;
-
-            match drawers_clone.get_mut(&id) {
-                Some(mut drawer) => {
-                    if let Some(buffer) =
-                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
-                    {
-                        buffer.write().push(DemoStep::Rectangle(
-                            id,
-                            NonNaN::<f32>::new(desired_x).unwrap_or_default(),
-                            NonNaN::<f32>::new(desired_y).unwrap_or_default(),
-                        ));
 
-                        return Ok(());
-                    }
+    // Returns whether the drawer's sprite is displayed.
+    let is_visible = lua_vm
+        .create_function(move |_, id: String| {
+            match drawers_clone.get(&id) {
+                Some(drawer) => Ok(drawer.visible),
+                None => {
+                    Err(Error::RuntimeError(format!(
+                        r#"The drawer with handle "{id}" doesn't exist."#
+                    )))
+                },
+            }
+        })
+        .unwrap();
 
-                    let current_position = drawer.pos;
+    let drawers_clone = drawers_handle.clone();
 
-                    let current_color = drawer.color;
-                    drawer.drawings.polygons.push(FilledPolygonPoints {
-                        points: vec![
-                            Vec3::new(current_position.x, current_position.y, 0.),
-                            Vec3::new(current_position.x + (desired_x), current_position.y, 0.),
-                            Vec3::new(
-                                current_position.x + (desired_x),
-                                current_position.y + (desired_y),
-                                0.,
-                            ),
-                            Vec3::new(current_position.x, current_position.y + (desired_y), 0.),
-                        ],
-                        color: current_color,
-                    });
-                },
+    // Returns the number of line segments the drawer has drawn.
+    let segment_count = lua_vm
+        .create_function(move |_, id: String| {
+            match drawers_clone.get(&id) {
+                Some(drawer) => Ok(drawer
+                    .drawings
+                    .lines
+                    .iter()
+                    .map(|line_strip| line_strip.points.len().saturating_sub(1))
+                    .sum::<usize>()),
                 None => {
-                    return Err(Error::RuntimeError(format!(
+                    Err(Error::RuntimeError(format!(
                         r#"The drawer with handle "{id}" doesn't exist."#
-                    )));
+                    )))
                 },
             }
-            Ok(())
         })
         .unwrap();
 
     let drawers_clone = drawers_handle.clone();
-    let demo_buffer_handle = demo_buffer.clone();
 
-    let point_to = lua_vm
-        .create_function(move |_, params: (String, f32, f32)| {
-            let (id, point_to_x, point_to_y) = params;
+    // Returns the lines and the polygons drawn by the drawer as lua tables.
+    let get_drawing = lua_vm
+        .create_function(move |lua, id: String| {
+            match drawers_clone.get(&id) {
+                Some(drawer) => {
+                    let lines = lua.create_table()?;
 
-            match drawers_clone.get_mut(&id) {
-                Some(mut drawer) => {
-                    if let Some(buffer) =
-                        demo_buffer_handle.get_state_if_eq(DemoBufferState::Record)
-                    {
-                        buffer.write().push(DemoStep::Rectangle(
-                            id,
-                            NonNaN::<f32>::new(point_to_x).unwrap_or_default(),
-                            NonNaN::<f32>::new(point_to_y).unwrap_or_default(),
-                        ));
+                    for line_strip in &drawer.drawings.lines {
+                        // Line strips with a single point have not been drawn yet.
+                        if line_strip.points.len() < 2 {
+                            continue;
+                        }
 
-                        return Ok(());
+                        let line = lua.create_table()?;
+
+                        line.set(
+                            "points",
+                            line_strip
+                                .points
+                                .iter()
+                                .map(|(pos, _, _)| [pos.x, pos.y])
+                                .collect::<Vec<[f32; 2]>>(),
+                        )?;
+                        line.set(
+                            "colors",
+                            line_strip
+                                .points
+                                .iter()
+                                .map(|(_, color, _)| color_into_srgba(*color))
+                                .collect::<Vec<[f32; 4]>>(),
+                        )?;
+                        line.set(
+                            "widths",
+                            line_strip
+                                .points
+                                .iter()
+                                .map(|(_, _, width)| *width)
+                                .collect::<Vec<f32>>(),
+                        )?;
+                        line.set("cap", line_strip.cap.to_string())?;
+                        line.set("join", line_strip.join.to_string())?;
+
+                        lines.push(line)?;
                     }
 
-                    let drawer_pos = drawer.pos;
-                    
-                    let dy = drawer_pos.y - point_to_y;
-                    let dx = drawer_pos.x - point_to_x;
+                    let polygons = lua.create_table()?;
+
+                    for polygon_points in &drawer.drawings.polygons {
+                        let polygon = lua.create_table()?;
+
+                        polygon.set(
+                            "points",
+                            polygon_points
+                                .points
+                                .iter()
+                                .map(|pos| [pos.x, pos.y])
+                                .collect::<Vec<[f32; 2]>>(),
+                        )?;
+                        polygon.set(
+                            "holes",
+                            polygon_points
+                                .holes
+                                .iter()
+                                .map(|hole| hole.iter().map(|pos| [pos.x, pos.y]).collect())
+                                .collect::<Vec<Vec<[f32; 2]>>>(),
+                        )?;
+                        polygon.set("color", color_into_srgba(polygon_points.color))?;
+
+                        polygons.push(polygon)?;
+                    }
 
-                    let atan = dy.atan2(dx);
+                    let drawing = lua.create_table()?;
 
-                    drawer.ang = Angle::from_degrees(atan.to_degrees() - 90.);
+                    drawing.set("lines", lines)?;
+                    drawing.set("polygons", polygons)?;
+
+                    Ok(drawing)
                 },
                 None => {
-                    return Err(Error::RuntimeError(format!(
+                    Err(Error::RuntimeError(format!(
                         r#"The drawer with handle "{id}" doesn't exist."#
-                    )));
+                    )))
                 },
             }
-
-            Ok(())
         })
         .unwrap();
 
@@ -1212,23 +3576,118 @@ pub fn init_lua_functions(
     lua_vm.globals().set("forward", forward).unwrap();
     lua_vm.globals().set("center", center).unwrap();
     lua_vm.globals().set("color", color).unwrap();
+    lua_vm.globals().set("hsv", hsv).unwrap();
+    lua_vm.globals().set("define_color", define_color).unwrap();
     lua_vm.globals().set("print", print).unwrap();
     lua_vm.globals().set("wipe", wipe).unwrap();
     lua_vm.globals().set("exists", exists).unwrap();
     lua_vm.globals().set("enable", enable).unwrap();
     lua_vm.globals().set("disable", disable).unwrap();
     lua_vm.globals().set("fill", fill).unwrap();
+    lua_vm.globals().set("fill_color", fill_color).unwrap();
+    lua_vm.globals().set("begin_fill", begin_fill).unwrap();
+    lua_vm.globals().set("end_fill", end_fill).unwrap();
     lua_vm.globals().set("notification", notification).unwrap();
+    lua_vm.globals().set("wait", wait.clone()).unwrap();
+    lua_vm.globals().set("sleep", wait).unwrap();
     lua_vm.globals().set("position", position).unwrap();
     lua_vm.globals().set("rectangle", rectangle).unwrap();
     lua_vm.globals().set("set_drawer_angle", set_drawer_angle).unwrap();
     lua_vm.globals().set("point_to", point_to).unwrap();
+    lua_vm.globals().set("pen_size", pen_size).unwrap();
+    lua_vm.globals().set("line_cap", line_cap).unwrap();
+    lua_vm.globals().set("line_join", line_join).unwrap();
+    lua_vm.globals().set("arc", arc).unwrap();
+    lua_vm.globals().set("circle", circle).unwrap();
+    lua_vm.globals().set("ellipse", ellipse).unwrap();
+    lua_vm.globals().set("curve_to", curve_to).unwrap();
+    lua_vm.globals().set("cubic_to", cubic_to).unwrap();
+    lua_vm.globals().set("spline", spline).unwrap();
+    lua_vm.globals().set("write", write).unwrap();
+    lua_vm.globals().set("set_costume", set_costume).unwrap();
+    lua_vm.globals().set("stamp", stamp).unwrap();
+    lua_vm.globals().set("pen_up", pen_up).unwrap();
+    lua_vm.globals().set("pen_down", pen_down).unwrap();
+    lua_vm.globals().set("hide", hide).unwrap();
+    lua_vm.globals().set("show", show).unwrap();
+    lua_vm.globals().set("go_to", go_to).unwrap();
+    lua_vm.globals().set("set_x", set_x).unwrap();
+    lua_vm.globals().set("set_y", set_y).unwrap();
+    lua_vm.globals().set("home", home).unwrap();
+    lua_vm.globals().set("heading", heading).unwrap();
+    lua_vm.globals().set("get_color", get_color).unwrap();
+    lua_vm.globals().set("is_enabled", is_enabled).unwrap();
+    lua_vm.globals().set("is_visible", is_visible).unwrap();
+    lua_vm.globals().set("segment_count", segment_count).unwrap();
+    lua_vm.globals().set("get_drawing", get_drawing).unwrap();
+
+    tasks::animate_walking_functions(&lua_vm, drawers_handle, animation_speed, script_tasks);
+}
+
+/// Returns an error if any of the coordinates is infinite or NaN, as nothing can be drawn through them.
+#[cfg(not(target_family = "wasm"))]
+fn check_finite_coordinates(coordinates: &[f32]) -> mlua::Result<()>
+{
+    if coordinates.iter().all(|coordinate| coordinate.is_finite()) {
+        Ok(())
+    }
+    else {
+        Err(Error::RuntimeError(String::from(
+            "The coordinates must be finite numbers.",
+        )))
+    }
+}
+
+/// Reads a color from the arguments of a lua function.
+/// The color is either given as red, green, blue and an optional alpha value (each ranging from 0.0 to 1.0), a table of these,
+/// or a string which is parsed with [`Palette::parse`].
+#[cfg(not(target_family = "wasm"))]
+fn color_from_lua_args(palette: &Palette, args: &[mlua::Value]) -> mlua::Result<Color>
+{
+    let components: Vec<f32> = match args {
+        [mlua::Value::String(text)] => {
+            let text = text.to_str()?.to_string();
+
+            return palette.parse(&text).ok_or_else(|| {
+                Error::RuntimeError(format!(
+                    r##"Invalid color "{text}", expected a color name or a hex color, ie. "red" or "#ff8800"."##
+                ))
+            });
+        },
+        [mlua::Value::Table(table)] => table.sequence_values::<f32>().collect::<mlua::Result<_>>()?,
+        args => {
+            args.iter()
+                .map(|value| {
+                    match value {
+                        mlua::Value::Number(number) => Ok(*number as f32),
+                        mlua::Value::Integer(integer) => Ok(*integer as f32),
+                        value => {
+                            Err(Error::RuntimeError(format!(
+                                "Invalid color argument, expected a number, got {}.",
+                                value.type_name()
+                            )))
+                        },
+                    }
+                })
+                .collect::<mlua::Result<_>>()?
+        },
+    };
+
+    match components[..] {
+        [red, green, blue] => Ok(Color::srgb(red, green, blue)),
+        [red, green, blue, alpha] => Ok(Color::srgba(red, green, blue, alpha)),
+        _ => {
+            Err(Error::RuntimeError(format!(
+                "Invalid color, expected a color name, a hex color, or 3 or 4 numbers, got {} numbers.",
+                components.len()
+            )))
+        },
+    }
 }
 
 #[cfg(target_family = "wasm")]
 pub fn init_lua_functions_wasm(
     mut lua_rt: ResMut<LuaRuntime>,
-    draw_requester: Res<DrawRequester>,
     drawers_handle: Drawers,
     output_list: Arc<RwLock<SetLenBuffer<ScriptLinePrompts>>>,
     demo_buffer: DemoBuffer<Vec<DemoStep>>,
@@ -1373,12 +3832,8 @@ pub fn init_lua_functions_wasm(
                     //Reset the drawer's position.
                     drawer.pos = Vec2::default();
 
-                    let drawer_color = drawer.color;
-
                     //Add the reseted pos to the drawer
-                    drawer.drawings.lines.push(LineStrip {
-                        points: vec![(Vec3::default(), drawer_color)],
-                    });
+                    drawer.begin_line_strip();
 
                     //Reset the drawer's angle.
                     drawer.ang = Angle::from_degrees(90.);
@@ -1453,7 +3908,7 @@ pub fn init_lua_functions_wasm(
                     }
 
                     // Set the drawer's color
-                    drawer.color = Color::linear_rgba(red, green, blue, alpha);
+                    drawer.color = Color::srgba(red, green, blue, alpha);
                 },
                 None => {
                     // Return the error
@@ -1506,9 +3961,6 @@ pub fn init_lua_functions_wasm(
                     // Get origin
                     let origin = drawer.pos;
 
-                    //Clone the color so we can move it into the lines' list.
-                    let drawer_color = drawer.color;
-
                     // Degrees into radians.
                     let angle_rad = drawer.ang.to_radians();
 
@@ -1522,15 +3974,11 @@ pub fn init_lua_functions_wasm(
                     let y = origin.y
                         + (amount_forward * floating_point_calculation_error(angle_rad.sin()));
 
-                    //Store the new position and the drawer's color if it is enabled
+                    //Store the new position with the drawer's color and pen size if it is enabled
                     if drawer.enabled {
-                        drawer
-                            .drawings
-                            .lines
-                            .last_mut()
-                            .unwrap()
-                            .points
-                            .push((Vec3::new(x, y, 0.), drawer_color));
+                        let point = drawer.line_point(Vec2::new(x, y));
+
+                        drawer.push_line_point(point);
                     }
 
                     //Set the new drawers position.
@@ -1558,15 +4006,7 @@ pub fn init_lua_functions_wasm(
                 return Ok(piccolo::CallbackReturn::Return);
             }
 
-            for mut drawer in drawers_clone.iter_mut() {
-                let drawer = drawer.value_mut();
-
-                let mut default_drawings = Drawings::default();
-                default_drawings.lines.push(LineStrip {
-                    points: vec![(Vec3::new(drawer.pos.x, drawer.pos.y, 0.), Color::WHITE)],
-                });
-                drawer.drawings = default_drawings;
-            }
+            drawers_clone.wipe();
 
             Ok(piccolo::CallbackReturn::Return)
         });
@@ -1650,9 +4090,7 @@ pub fn init_lua_functions_wasm(
 
                     drawer.enabled = true;
 
-                    let tuple = (Vec3::new(drawer.pos.x, drawer.pos.y, 0.), drawer.color);
-
-                    drawer.drawings.lines.push(LineStrip::new(vec![tuple]));
+                    drawer.begin_line_strip();
                 },
                 None => {
                     return Err(anyhow::Error::msg(format!(
@@ -1701,7 +4139,6 @@ pub fn init_lua_functions_wasm(
         });
     
         let drawers_clone = drawers_handle.clone();
-        let draw_request_sender = draw_requester.sender.clone();
         let demo_buffer_handle = demo_buffer.clone();
 
         let fill = Callback::from_fn(&ctx, move |_, _, mut stack| {
@@ -1713,66 +4150,27 @@ pub fn init_lua_functions_wasm(
 
             let id = id.to_string();
 
-            match drawers_clone.get(&id) {
-                Some(selected_drawer) => {
+            // The drawer's handle is released before the lines of every drawer are read.
+            let drawer_info = drawers_clone
+                .get(&id)
+                .map(|drawer| (drawer.pos, drawer.current_fill_color()));
+
+            match drawer_info {
+                Some((position, fill_color)) => {
                     if let Some(buffer) = demo_buffer_handle.get_state_if_eq(DemoBufferState::Record) {
                         buffer.write().push(DemoStep::Fill(id));
 
                         return Ok(piccolo::CallbackReturn::Return);
                     }
 
-                    let drawer_lines: Vec<Vec3> = drawers_clone
-                        .iter()
-                        .flat_map(|pair| {
-                            let drawer = pair.value();
-
-                            drawer.drawings.clone().lines.iter().flat_map(|line_strip| {
-                                line_strip.points.iter().map(|points| points.0).collect::<Vec<Vec3>>()
-                            }).collect::<Vec<Vec3>>()
-                        })
-                        .collect();
-
-                    let mut lines: Vec<Line> = vec![];
-
-                    for positions in dbg!(drawer_lines).windows(2) {
-                        let (min, max) = (positions[0], positions[1]);
-
-                        lines.push(Line::new(min, max));
-                    }
-
-                    let mut checked_lines: Vec<Line> = vec![];
-
-                    for (idx, line) in lines.iter().enumerate() {
-                        for (current_checked_idx, checked_line) in checked_lines.iter().enumerate() {
-                            if idx as isize - 1 != current_checked_idx as isize && checked_lines.len() > 2 {
-                                if let Some(intersection_pos) = line.intersects(checked_line) {
-                                    let intersected_line_idx = checked_lines.iter().position(|line| line == checked_line).unwrap();
-                                    let mut polygon_points: Vec<Coord> = vec![];
-
-                                    polygon_points.push(coord!{x: intersection_pos.x as f64, y: intersection_pos.y as f64});
-
-                                    for poly_line in &checked_lines[intersected_line_idx + 1..idx] {
-                                        polygon_points.push(coord! {x: poly_line.max.x as f64, y: poly_line.max.y as f64});
-                                    }
-
-                                    let polygon = Polygon::new(LineString::new(polygon_points.clone()), vec![]);
-
-                                    let poly_convex_hull = polygon.convex_hull();
-
-                                    if poly_convex_hull.contains(&point!(x: selected_drawer.pos.x as f64, y: selected_drawer.pos.y as f64)) {
-                                        draw_request_sender.send((polygon_points.iter().map(|coord| Vec3::new(coord.x as f32, coord.y as f32, 0.)).collect::<Vec<Vec3>>(), selected_drawer.color, id.clone())).unwrap();
-                                    }
-
-                                    break;
-                                }
-                            }
+                    if let Some(polygon) = drawers_clone.fill_region(position, fill_color) {
+                        if let Some(mut drawer) = drawers_clone.get_mut(&id) {
+                            drawer.push_polygon(polygon);
                         }
-
-                        checked_lines.push(line.clone());
                     }
                 },
                 None => {
-                    return Err(anyhow::Error::msg(format!(r#"The drawer with handle "{id}" already exists."#)).into());
+                    return Err(anyhow::Error::msg(format!(r#"The drawer with handle "{id}" doesn't exist."#)).into());
                 },
             }
 
@@ -1872,9 +4270,9 @@ pub fn init_lua_functions_wasm(
 
                     let current_position = drawer.pos;
 
-                    let current_color = drawer.color;
-                    drawer.drawings.polygons.push(FilledPolygonPoints {
-                        points: vec![
+                    let current_color = drawer.current_fill_color();
+                    drawer.push_polygon(FilledPolygonPoints::new(
+                        vec![
                             Vec3::new(current_position.x, current_position.y, 0.),
                             Vec3::new(current_position.x + (desired_x), current_position.y, 0.),
                             Vec3::new(
@@ -1884,8 +4282,8 @@ pub fn init_lua_functions_wasm(
                             ),
                             Vec3::new(current_position.x, current_position.y + (desired_y), 0.),
                         ],
-                        color: current_color,
-                    });
+                        current_color,
+                    ));
                 },
                 None => {
                     return Err(anyhow::Error::msg(format!(
@@ -1923,6 +4321,14 @@ pub enum CallbackType
     OnDraw,
     OnInput,
     OnParameterChange,
+    OnClick,
+    OnMouseDown,
+    OnMouseUp,
+    OnDrag,
+    OnKeyPressed,
+    OnKeyReleased,
+    OnTick,
+    OnFixedTick,
 }
 
 impl Display for CallbackType
@@ -1933,6 +4339,14 @@ impl Display for CallbackType
             CallbackType::OnDraw => "on_draw",
             CallbackType::OnInput => "on_input",
             CallbackType::OnParameterChange => "on_param_change",
+            CallbackType::OnClick => "on_click",
+            CallbackType::OnMouseDown => "on_mouse_down",
+            CallbackType::OnMouseUp => "on_mouse_up",
+            CallbackType::OnDrag => "on_drag",
+            CallbackType::OnKeyPressed => "on_key_pressed",
+            CallbackType::OnKeyReleased => "on_key_released",
+            CallbackType::OnTick => "on_tick",
+            CallbackType::OnFixedTick => "on_fixed_tick",
         })
     }
 }
@@ -1959,87 +4373,411 @@ pub struct Line
     pub max: Vec3,
 }
 
-pub enum IntersectType
-{
-    Connected(Vec3),
-    Intersected(Vec3),
-}
-
 impl Line
 {
     pub fn new(min: Vec3, max: Vec3) -> Self
     {
         Self { min, max }
     }
+}
+
+pub fn floating_point_calculation_error(float: f32) -> f32
+{
+    if float.abs() < 0.000001 {
+        0.0
+    }
+    else {
+        float
+    }
+}
+
+#[cfg(all(test, not(target_family = "wasm")))]
+mod tests
+{
+    use bevy::ecs::{system::RunSystemOnce, world::World};
+
+    use super::*;
+    use crate::parameters::ParameterValue;
+
+    /// The code which reaches outside of the sandbox, none of these should succeed in an untrusted project.
+    const ESCAPES: &[&str] = &[
+        "return io.open",
+        "return package.loadlib",
+        "return require",
+        "return os.execute",
+        "return load(string.dump(function() return 42 end))()",
+        "return loadstring(string.dump(function() return 42 end))()",
+    ];
+
+    /// Returns whether the code runs without an error and returns something.
+    fn escapes(lua_runtime: &LuaRuntime, code: &str) -> bool
+    {
+        lua_runtime
+            .load(code)
+            .eval::<mlua::Value>()
+            .is_ok_and(|value| !value.is_nil())
+    }
 
-    pub fn intersects(&self, other_line: &Self) -> Option<Vec3>
+    #[test]
+    fn untrusted_scripts_are_sandboxed()
     {
-        if self == other_line {
-            return None;
+        let lua_runtime = LuaRuntime::default();
+
+        for code in ESCAPES {
+            assert!(!escapes(&lua_runtime, code), "{code}");
         }
 
-        // Slope of Line 1 (m1) and Line 2 (m2)
-        let mut m1 = (self.max.y - self.min.y) / (self.max.x - self.min.x);
+        // Bytecode can't be loaded even if it was dumped before the project was untrusted.
+        lua_runtime.set_trusted(true).unwrap();
+
+        let bytecode = lua_runtime
+            .load("return string.dump(function() return 42 end)")
+            .eval::<mlua::String>()
+            .unwrap();
+
+        lua_runtime.set_trusted(false).unwrap();
+        lua_runtime.globals().set("bytecode", bytecode).unwrap();
+
+        assert!(!escapes(&lua_runtime, "return load(bytecode)()"));
+        assert!(!escapes(
+            &lua_runtime,
+            "return load(function() local chunk = bytecode bytecode = nil return chunk end)()"
+        ));
+
+        // Source code can still be loaded.
+        assert_eq!(
+            lua_runtime
+                .load("return load('return 42')()")
+                .eval::<i32>()
+                .unwrap(),
+            42
+        );
+    }
 
-        if (self.max.x - self.min.x) == 0. {
-            m1 = 0.;
-        }
+    #[test]
+    fn trusted_scripts_are_not_sandboxed()
+    {
+        let lua_runtime = LuaRuntime::default();
 
-        let mut m2 = (other_line.max.y - other_line.min.y) / (other_line.max.x - other_line.min.x);
+        lua_runtime.set_trusted(true).unwrap();
 
-        if (other_line.max.x - other_line.min.x) == 0. {
-            m2 = 0.;
+        for code in ESCAPES {
+            assert!(escapes(&lua_runtime, code), "{code}");
         }
 
-        if other_line.max == self.min || self.min == other_line.min {
-            return Some(Vec3::new(self.min.x, self.min.y, 0.));
-        }
+        lua_runtime.set_trusted(false).unwrap();
 
-        if self.max == other_line.min || self.max == other_line.max {
-            return Some(Vec3::new(self.max.x, self.max.y, 0.));
+        for code in ESCAPES {
+            assert!(!escapes(&lua_runtime, code), "{code}");
         }
+    }
 
-        // Check if the lines are parallel (i.e., have the same slope)
-        if m1 == m2 {
-            return None;
-        }
+    /// Creates a lua runtime with all the functions of the app, which are limited by the execution limits.
+    fn limited_lua_runtime(execution_limits: ExecutionLimits) -> (LuaRuntime, Drawers)
+    {
+        let (lua_runtime, drawers, _) = animated_lua_runtime(execution_limits, AnimationSpeed(0));
 
-        // Calculate the y-intercepts (b1 and b2) of the two lines
-        let b1 = floating_point_calculation_error(self.min.y - m1 * self.min.x);
-        let b2 = floating_point_calculation_error(other_line.min.y - m2 * other_line.min.x);
+        (lua_runtime, drawers)
+    }
 
-        // Calculate the x-coordinate of the intersection point
-        let intersection_x = (b2 - b1) / (m1 - m2);
+    /// Creates a lua runtime with all the functions of the app, the drawers walk with the given speed if the code is run as a task.
+    fn animated_lua_runtime(
+        execution_limits: ExecutionLimits,
+        animation_speed: AnimationSpeed,
+    ) -> (LuaRuntime, Drawers, tasks::ScriptTasks)
+    {
+        let ui_state = ui::UiState::default();
 
-        // Calculate the y-coordinate of the intersection point using either line's equation
-        let intersection_y = m1 * intersection_x + b1;
-        let intersection_point = Vec3::new(intersection_x, intersection_y, 0.);
+        *ui_state.execution_limits.write() = execution_limits;
+        *ui_state.animation_speed.write() = animation_speed;
 
-        if !self.is_point_on_segment(&intersection_point)
-            || !other_line.is_point_on_segment(&intersection_point)
-        {
-            return None;
+        ui_lua_runtime(&ui_state)
+    }
+
+    /// Creates a lua runtime with all the functions of the app, which use the handles of the UI state.
+    fn ui_lua_runtime(ui_state: &ui::UiState) -> (LuaRuntime, Drawers, tasks::ScriptTasks)
+    {
+        let mut world = World::new();
+
+        world.init_resource::<LuaRuntime>();
+
+        let drawers = Drawers::new();
+        let drawers_handle = drawers.clone();
+        let script_tasks = tasks::ScriptTasks::default();
+        let script_tasks_handle = script_tasks.clone();
+        let command_line_outputs = ui_state.command_line_outputs.clone();
+        let demo_buffer = ui_state.demo_buffer.clone();
+        let toasts = ui_state.toasts.clone();
+        let project_handles = ProjectHandles {
+            image_library: ui_state.images.clone(),
+            palette: ui_state.palette.clone(),
+            animation_speed: ui_state.animation_speed.clone(),
+            execution_limits: ui_state.execution_limits.clone(),
+        };
+
+        world
+            .run_system_once(move |lua_runtime: ResMut<LuaRuntime>| {
+                init_lua_functions(
+                    lua_runtime,
+                    drawers_handle.clone(),
+                    command_line_outputs.clone(),
+                    demo_buffer.clone(),
+                    toasts.clone(),
+                    project_handles.clone(),
+                    script_tasks_handle.clone(),
+                );
+            })
+            .unwrap();
+
+        (world.resource::<LuaRuntime>().clone(), drawers, script_tasks)
+    }
+
+    /// Counts the points of every drawer's drawings.
+    fn recount_points(drawers: &Drawers) -> usize
+    {
+        drawers.iter().map(|drawer| drawer.point_count()).sum()
+    }
+
+    #[test]
+    fn drawers_and_points_are_limited()
+    {
+        let (lua_runtime, drawers) = limited_lua_runtime(ExecutionLimits {
+            max_drawers: 2,
+            max_points: 10,
+            ..Default::default()
+        });
+
+        lua_runtime
+            .load(r#"new("first") new("second")"#)
+            .exec()
+            .unwrap();
+
+        let error = lua_runtime.load(r#"new("third")"#).exec().unwrap_err();
+
+        assert!(error.to_string().contains("drawers"));
+        assert_eq!(drawers.len(), 2);
+
+        let error = lua_runtime
+            .load(r#"for _ = 1, 100 do forward("first", 1) end"#)
+            .exec()
+            .unwrap_err();
+
+        assert!(error.to_string().contains("points"));
+        assert_eq!(drawers.point_count(), 10);
+        assert_eq!(drawers.point_count(), recount_points(&drawers));
+
+        lua_runtime.load("wipe()").exec().unwrap();
+
+        assert_eq!(drawers.point_count(), recount_points(&drawers));
+
+        // The texts and the stamps count as one point each.
+        for code in [
+            r#"for _ = 1, 100 do stamp("first") end"#,
+            r#"for _ = 1, 100 do write("first", "text", 10) end"#,
+        ] {
+            let error = lua_runtime.load(code).exec().unwrap_err();
+
+            assert!(error.to_string().contains("points"));
+            assert_eq!(drawers.point_count(), 10);
+            assert_eq!(drawers.point_count(), recount_points(&drawers));
+
+            lua_runtime.load("wipe()").exec().unwrap();
         }
 
-        Some(Vec3::new(intersection_x, intersection_y, 0.))
+        lua_runtime.load(r#"remove("second")"#).exec().unwrap();
+
+        assert_eq!(drawers.point_count(), recount_points(&drawers));
     }
 
-    fn is_point_on_segment(&self, point: &Vec3) -> bool
+    #[test]
+    fn memory_is_limited()
     {
-        let within_x_bounds =
-            point.x >= self.min.x.min(self.max.x) && point.x <= self.min.x.max(self.max.x);
-        let within_y_bounds =
-            point.y >= self.min.y.min(self.max.y) && point.y <= self.min.y.max(self.max.y);
-        within_x_bounds && within_y_bounds
+        let (lua_runtime, _) = limited_lua_runtime(ExecutionLimits {
+            max_memory_megabytes: 64,
+            ..Default::default()
+        });
+
+        let error = lua_runtime
+            .load(r#"local text = string.rep("x", 200 * 1024 * 1024)"#)
+            .exec()
+            .unwrap_err();
+
+        assert!(matches!(error, mlua::Error::MemoryError(_)));
+
+        // The runtime can still be used after running out of memory.
+        assert_eq!(lua_runtime.load("return 1 + 1").eval::<i32>().unwrap(), 2);
     }
-}
 
-pub fn floating_point_calculation_error(float: f32) -> f32
-{
-    if float.abs() < 0.000001 {
-        0.0
+    #[test]
+    fn cancelled_walks_are_finished()
+    {
+        let (lua_runtime, drawers, script_tasks) = animated_lua_runtime(
+            ExecutionLimits::default(),
+            AnimationSpeed(AnimationSpeed::MAX),
+        );
+
+        lua_runtime
+            .load(r#"new("walking") new("instant") circle("instant", 100, true)"#)
+            .exec()
+            .unwrap();
+
+        script_tasks.spawn(
+            &lua_runtime,
+            r#"circle("walking", 100, true)"#,
+            tasks::TaskOrigin::CommandLine,
+            None,
+        );
+
+        // The drawer starts walking, and is stopped after a short distance.
+        for _ in 0..3 {
+            assert!(script_tasks.poll(&lua_runtime).is_empty());
+        }
+
+        script_tasks.cancel(&tasks::TaskOrigin::CommandLine);
+
+        let walking = drawers.get("walking").unwrap();
+        let instant = drawers.get("instant").unwrap();
+
+        let points = |drawer: &Drawer| -> Vec<Vec<LinePoint>> {
+            drawer
+                .drawings
+                .lines
+                .iter()
+                .map(|line| line.points.clone())
+                .collect()
+        };
+
+        assert_eq!(walking.pos, instant.pos);
+        assert_eq!(points(&walking), points(&instant));
+        assert_eq!(walking.drawings.polygons.len(), 1);
+        assert_eq!(
+            walking.drawings.polygons[0].points,
+            instant.drawings.polygons[0].points
+        );
+
+        drop((walking, instant));
+
+        assert_eq!(drawers.point_count(), recount_points(&drawers));
     }
-    else {
-        float
+
+    #[test]
+    fn demos_are_recorded_with_the_parameters()
+    {
+        let ui_state = ui::UiState::default();
+        let (lua_runtime, _, _) = ui_lua_runtime(&ui_state);
+        let script_instance = ui::ScriptInstance::new(
+            "Polygon".to_string(),
+            r#"
+                local sides = param("sides", 5, 3, 12)
+                local size = param("size", 50, 10, 200)
+
+                new("polygon")
+
+                for _ = 1, sides do
+                    forward("polygon", size)
+                    rotate("polygon", 360 / sides)
+                end
+
+                after(1, function() wipe() end)
+
+                function on_tick() end
+            "#
+            .to_string(),
+        );
+
+        let sides = ParameterValue::Number {
+            value: 3.,
+            min: Some(3.),
+            max: Some(12.),
+        };
+
+        script_instance.parameters.declare("sides", sides);
+        ui_state.demo_buffer.set_state(DemoBufferState::Record);
+
+        let environment = script_instance
+            .create_demo_environment(&lua_runtime, ui_state.palette.clone())
+            .unwrap();
+
+        lua_runtime
+            .execute_code_in(&script_instance.script, environment)
+            .unwrap();
+
+        // The current value of a parameter is used, and the default value of the parameters which don't exist yet.
+        let forwards: Vec<f32> = ui_state
+            .demo_buffer
+            .buffer
+            .read()
+            .iter()
+            .filter_map(|step| {
+                match step {
+                    DemoStep::Forward(_, amount) => Some(amount.get()),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        assert_eq!(forwards, vec![50.; 3]);
+
+        // Recording the demo doesn't declare any parameters, or define any callbacks in the global table.
+        let parameters: Vec<(String, ParameterValue)> = script_instance
+            .parameters
+            .read()
+            .iter()
+            .map(|parameter| (parameter.name.clone(), parameter.value))
+            .collect();
+
+        assert_eq!(parameters, vec![("sides".to_string(), sides)]);
+        assert!(lua_runtime
+            .globals()
+            .get::<mlua::Value>("on_tick")
+            .unwrap()
+            .is_nil());
+    }
+
+    #[test]
+    fn drawings_are_compared_without_revisions()
+    {
+        let line = LineStrip::new(
+            vec![(Vec3::ZERO, Color::WHITE, 1.)],
+            LineCap::Round,
+            LineJoin::Bevel,
+        );
+        let polygon = FilledPolygonPoints::new(vec![Vec3::ZERO, Vec3::X, Vec3::Y], Color::WHITE);
+
+        assert_eq!(
+            line,
+            LineStrip::new(
+                vec![(Vec3::ZERO, Color::WHITE, 1.)],
+                LineCap::Round,
+                LineJoin::Bevel
+            )
+        );
+        assert_eq!(
+            polygon,
+            FilledPolygonPoints::new(vec![Vec3::ZERO, Vec3::X, Vec3::Y], Color::WHITE)
+        );
+        assert_eq!(LineStrip::default(), LineStrip::default());
+
+        // The drawers are equal even if one of them was borrowed mutably.
+        let drawers = Drawers::new();
+
+        drawers.insert("first".to_string(), Drawer::default());
+        drawers.insert("second".to_string(), Drawer::default());
+
+        drawers.get_mut("first").unwrap().pos = Vec2::ONE;
+        drawers.get_mut("first").unwrap().pos = Vec2::ZERO;
+
+        let first = drawers.get("first").unwrap();
+        let second = drawers.get("second").unwrap();
+
+        assert_ne!(first.revision(), second.revision());
+        assert_eq!(*first, *second);
+
+        // Every drawing gets a new revision, even the default ones.
+        assert_ne!(LineStrip::default().revision(), LineStrip::default().revision());
+        assert_ne!(
+            FilledPolygonPoints::default().revision(),
+            FilledPolygonPoints::default().revision()
+        );
     }
 }
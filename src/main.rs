@@ -2,22 +2,23 @@
 
 use bevy::{
     asset::embedded_asset,
-    math::Quat,
-    prelude::PluginGroup,
+    color::Color,
+    math::{Quat, Vec2},
+    prelude::{Image, PluginGroup},
     text::cosmic_text::Angle,
     window::{Window, WindowPlugin},
 };
-use std::{fs, path::PathBuf};
+use std::{collections::HashMap, fs, path::PathBuf};
 
 // hide console window on Windows in release
 use bevy::{
-    app::{App, AppExit, PreUpdate, Startup, Update},
-    asset::{AssetServer, Assets},
-    math::vec3,
+    app::{App, AppExit, Startup, Update},
+    asset::{AssetServer, Assets, Handle},
     prelude::{
-        Camera2d, Commands, Entity, EventReader, Mesh, Mesh2d, Query, Res, ResMut, Transform, With,
+        Camera2d, Commands, DetectChanges, Entity, EventReader, Local, Mesh, Mesh2d, Res, ResMut,
+        Text2d, TextColor, TextFont, Transform,
     },
-    sprite::{ColorMaterial, MeshMaterial2d, Sprite},
+    sprite::{Anchor, ColorMaterial, MeshMaterial2d, Sprite},
     DefaultPlugins,
 };
 use bevy_egui::EguiPlugin;
@@ -25,17 +26,32 @@ use bevy_egui::EguiPlugin;
 use ferris_draw::LuaRuntime;
 
 #[cfg(not(target_family = "wasm"))]
-use ferris_draw::init_lua_functions;
+use bevy::{
+    app::FixedUpdate,
+    prelude::{Fixed, IntoSystemConfigs, Time},
+};
+#[cfg(not(target_family = "wasm"))]
+use ferris_draw::{
+    init_lua_functions,
+    tasks::ScriptTasks,
+    ui::{canvas_pointer_callbacks, fixed_tick_callbacks, tick_callbacks, timer_callbacks},
+    ProjectHandles, FIXED_TICKS_PER_SECOND,
+};
 
 #[cfg(target_family = "wasm")]
 use ferris_draw::init_lua_functions_wasm;
 
 use ferris_draw::{
     ui::{main_ui, UiState},
-    DrawRequester, DrawerMesh, Drawers, FilledPolygonPoints,
+    Costumes, DrawerMesh, Drawers, FilledPolygonPoints, LineStrip, Stamp, TextLabel,
 };
+use egui_toast::{Toast, ToastKind};
 use miniz_oxide::deflate::CompressionLevel;
 
+/// The size of the longer side of a drawer's sprite.
+/// This matches the size of Ferris at the scale it has always been displayed at.
+const COSTUME_SIZE: f32 = 25.6;
+
 fn main()
 {
     let mut app = App::new();
@@ -51,15 +67,23 @@ fn main()
     .add_plugins(EguiPlugin)
     .init_resource::<UiState>()
     .init_resource::<Drawers>()
-    .init_resource::<DrawRequester>()
+    .init_resource::<Costumes>()
     .add_systems(Startup, setup)
-    .add_systems(PreUpdate, clear_screen)
     .add_systems(Update, main_ui)
+    .add_systems(Update, load_costumes)
     .add_systems(Update, draw)
     .add_systems(Update, exit_handler);
 
     app.init_resource::<LuaRuntime>();
 
+    #[cfg(not(target_family = "wasm"))]
+    app.init_resource::<ScriptTasks>()
+        .insert_resource(Time::<Fixed>::from_hz(FIXED_TICKS_PER_SECOND))
+        .add_systems(Update, canvas_pointer_callbacks.after(main_ui))
+        .add_systems(Update, tick_callbacks.after(main_ui))
+        .add_systems(Update, timer_callbacks.after(main_ui))
+        .add_systems(FixedUpdate, fixed_tick_callbacks);
+
     embedded_asset!(app, "../assets/ferris.png");
 
     app.run();
@@ -69,8 +93,8 @@ fn setup(
     mut commands: Commands,
     drawers: Res<Drawers>,
     mut ui_state: ResMut<UiState>,
-    draw_requested: Res<DrawRequester>,
     lua_runtime: ResMut<LuaRuntime>,
+    #[cfg(not(target_family = "wasm"))] script_tasks: Res<ScriptTasks>,
 )
 {
     //Load in save
@@ -97,21 +121,32 @@ fn setup(
     let toast_handle = ui_state.toasts.clone();
     let demo_buffer_handle = ui_state.demo_buffer.clone();
 
+    // The saved project is never trusted at startup, the user is asked whether to trust it instead.
+    #[cfg(not(target_family = "wasm"))]
+    {
+        ui_state.trust_prompt = ui_state.requests_trust;
+    }
+
     // Lua function wont be initalized in a wasm environment as they wont be needed.
     #[cfg(not(target_family = "wasm"))]
     init_lua_functions(
         lua_runtime,
-        draw_requested,
         drawers.clone(),
         ui_state.command_line_outputs.clone(),
         demo_buffer_handle,
         toast_handle,
+        ProjectHandles {
+            image_library: ui_state.images.clone(),
+            palette: ui_state.palette.clone(),
+            animation_speed: ui_state.animation_speed.clone(),
+            execution_limits: ui_state.execution_limits.clone(),
+        },
+        script_tasks.clone(),
     );
 
     #[cfg(target_family = "wasm")]
     init_lua_functions_wasm(
         lua_runtime,
-        draw_requested,
         drawers.clone(),
         ui_state.command_line_outputs.clone(),
         demo_buffer_handle,
@@ -140,70 +175,376 @@ fn exit_handler(exit_events: EventReader<AppExit>, ui_state: Res<UiState>)
     }
 }
 
+/// The revision of a line strip or a polygon when it was last drawn, with the entity and the mesh displaying it.
+/// The entity is [`None`] if the shape doesn't have anything to display.
+type DrawnShape = (u64, Option<(Entity, Handle<Mesh>)>);
+
+/// The entities displaying a drawer on the canvas.
+#[derive(Default)]
+struct DrawnDrawer
+{
+    /// The revision of the drawer when it was last drawn, this is [`None`] if the drawer hasn't been drawn yet.
+    revision: Option<u64>,
+
+    lines: Vec<DrawnShape>,
+    polygons: Vec<DrawnShape>,
+
+    /// The texts and the stamps with their revisions, these are kept until they are removed from the drawings.
+    texts: Vec<(u64, Entity)>,
+    stamps: Vec<(u64, Entity)>,
+
+    /// The sprite of the drawer itself, this is [`None`] if the drawer is hidden.
+    sprite: Option<Entity>,
+}
+
+impl DrawnDrawer
+{
+    /// Despawns the texts, the stamps and the sprite of the drawer.
+    fn despawn_sprites(&mut self, commands: &mut Commands)
+    {
+        let sprites = self
+            .texts
+            .drain(..)
+            .chain(self.stamps.drain(..))
+            .map(|(_, entity)| entity)
+            .chain(self.sprite.take());
+
+        for entity in sprites {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// The drawers displayed on the canvas, by their handles.
+/// The entities are kept between the frames, and they are only updated when their drawer has changed.
+#[derive(Default)]
+struct Canvas
+{
+    drawers: HashMap<String, DrawnDrawer>,
+
+    /// The material of every mesh, the colors are stored in the meshes' vertices so the material must not tint them.
+    material: Option<Handle<ColorMaterial>>,
+}
+
 fn draw(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     drawers: Res<Drawers>,
-    draw_requester: Res<DrawRequester>,
     asset_server: Res<AssetServer>,
+    costumes: Res<Costumes>,
+    mut canvas: Local<Canvas>,
 )
 {
-    // Try to receive draw requests from the lua runtime
-    if let Ok((points, color, id)) = draw_requester.receiver.lock().try_recv() {
-        if let Some(mut drawer) = drawers.get_mut(&id) {
-            drawer
-                .drawings
-                .polygons
-                .push(FilledPolygonPoints::new(points, color));
+    let canvas = &mut *canvas;
+
+    let material = canvas
+        .material
+        .get_or_insert_with(|| materials.add(Color::WHITE))
+        .clone();
+
+    // Remove the drawers which no longer exist
+    canvas.drawers.retain(|id, drawn_drawer| {
+        if drawers.contains_key(id) {
+            return true;
         }
-    }
+
+        let shapes = drawn_drawer
+            .lines
+            .drain(..)
+            .filter_map(|(_, drawn)| drawn)
+            .chain(drawn_drawer.polygons.drain(..).filter_map(|(_, drawn)| drawn));
+
+        for (entity, _mesh) in shapes {
+            commands.entity(entity).despawn();
+        }
+
+        drawn_drawer.despawn_sprites(&mut commands);
+
+        false
+    });
 
     for drawer in drawers.iter() {
-        let (_id, drawer_info) = drawer.pair();
+        let (id, drawer_info) = drawer.pair();
+
+        let drawn_drawer = canvas.drawers.entry(id.clone()).or_default();
+
+        // Static drawers don't cost anything, unless the costumes have been reloaded.
+        if drawn_drawer.revision == Some(drawer_info.revision()) && !costumes.is_changed() {
+            continue;
+        }
 
-        for line_strip in &drawer_info.drawings.lines {
-            let mesh = Mesh::from(line_strip.clone());
+        drawn_drawer.revision = Some(drawer_info.revision());
+
+        // Lines without a single visible segment dont have to be drawn.
+        update_shapes(
+            &mut commands,
+            &mut meshes,
+            &material,
+            &mut drawn_drawer.lines,
+            &drawer_info.drawings.lines,
+            LineStrip::revision,
+            |mesh| mesh.count_vertices() != 0,
+        );
+
+        // Polygons without a single triangle dont have to be drawn.
+        update_shapes(
+            &mut commands,
+            &mut meshes,
+            &material,
+            &mut drawn_drawer.polygons,
+            &drawer_info.drawings.polygons,
+            FilledPolygonPoints::revision,
+            |mesh| mesh.indices().is_some_and(|indices| !indices.is_empty()),
+        );
+
+        // The stamps and the drawer display the costumes, so they are respawned if the costumes have been reloaded.
+        if costumes.is_changed() {
+            let sprites = drawn_drawer
+                .stamps
+                .drain(..)
+                .map(|(_, entity)| entity)
+                .chain(drawn_drawer.sprite.take());
+
+            for entity in sprites {
+                commands.entity(entity).despawn();
+            }
+        }
 
-            let shape = meshes.add(mesh);
+        update_sprites(
+            &mut commands,
+            &mut drawn_drawer.texts,
+            &drawer_info.drawings.texts,
+            TextLabel::revision,
+            |commands, label| {
+                commands
+                    .spawn((
+                        Text2d::new(label.text.clone()),
+                        TextFont::from_font_size(label.size),
+                        TextColor(label.color),
+                        // The text starts from the drawer's position.
+                        Anchor::BottomLeft,
+                        sprite_transform(label.pos, &label.ang, 0.),
+                        DrawerMesh,
+                    ))
+                    .id()
+            },
+        );
+
+        update_sprites(
+            &mut commands,
+            &mut drawn_drawer.stamps,
+            &drawer_info.drawings.stamps,
+            Stamp::revision,
+            |commands, stamp| {
+                commands
+                    .spawn((
+                        costume_sprite(&asset_server, &costumes, stamp.costume.as_deref()),
+                        sprite_transform(stamp.pos, &stamp.ang, 0.),
+                        DrawerMesh,
+                    ))
+                    .id()
+            },
+        );
 
-            commands.spawn((
-                Mesh2d(shape),
-                MeshMaterial2d(materials.add(drawer_info.color)),
-                DrawerMesh,
-            ));
+        // Hidden drawers only leave their drawings on the canvas.
+        // The drawer is displayed above its drawings, its sprite is only moved when the drawer moves.
+        match (drawer_info.visible, drawn_drawer.sprite) {
+            (true, Some(entity)) => {
+                commands.entity(entity).insert((
+                    costume_sprite(&asset_server, &costumes, drawer_info.costume.as_deref()),
+                    sprite_transform(drawer_info.pos, &drawer_info.ang, 1.),
+                ));
+            },
+            (true, None) => {
+                drawn_drawer.sprite = Some(
+                    commands
+                        .spawn((
+                            costume_sprite(
+                                &asset_server,
+                                &costumes,
+                                drawer_info.costume.as_deref(),
+                            ),
+                            sprite_transform(drawer_info.pos, &drawer_info.ang, 1.),
+                            DrawerMesh,
+                        ))
+                        .id(),
+                );
+            },
+            (false, Some(entity)) => {
+                commands.entity(entity).despawn();
+                drawn_drawer.sprite = None;
+            },
+            (false, None) => (),
         }
+    }
+}
 
-        for polygon in &drawer_info.drawings.polygons {
-            let mesh = Mesh::from(polygon.clone());
+/// Updates the meshes of the line strips or the polygons of a drawer, only the shapes whose revision has changed since they were last drawn are rebuilt.
+fn update_shapes<T>(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    material: &Handle<ColorMaterial>,
+    drawn_shapes: &mut Vec<DrawnShape>,
+    shapes: &[T],
+    revision: fn(&T) -> u64,
+    is_visible: fn(&Mesh) -> bool,
+) where
+    T: Clone + Into<Mesh>,
+{
+    // Remove the shapes which no longer exist
+    for (_, drawn) in drawn_shapes.drain(shapes.len().min(drawn_shapes.len())..) {
+        if let Some((entity, _mesh)) = drawn {
+            commands.entity(entity).despawn();
+        }
+    }
 
-            let shape = meshes.add(mesh);
+    for (idx, shape) in shapes.iter().enumerate() {
+        let shape_revision = revision(shape);
 
-            commands.spawn((
-                Mesh2d(shape),
-                MeshMaterial2d(materials.add(drawer_info.color)),
-                DrawerMesh,
-            ));
+        if drawn_shapes
+            .get(idx)
+            .is_some_and(|(drawn_revision, _)| *drawn_revision == shape_revision)
+        {
+            continue;
         }
 
-        let icon: bevy::prelude::Handle<bevy::prelude::Image> =
-            asset_server.load("embedded://ferris_draw/../assets/ferris.png");
-
-        commands.spawn((
-            Sprite::from_image(icon),
-            Transform::from_xyz(drawer_info.pos.x, drawer_info.pos.y, 0.)
-                .with_rotation(Quat::from_rotation_z(
-                    Angle::from_degrees(drawer.ang.to_degrees() - 90.).to_radians(),
-                ))
-                .with_scale(vec3(0.1, 0.1, 1.)),
-            DrawerMesh,
-        ));
+        let mesh: Mesh = shape.clone().into();
+        let mesh = is_visible(&mesh).then_some(mesh);
+
+        let previous = drawn_shapes
+            .get_mut(idx)
+            .and_then(|(_, drawn)| drawn.take());
+
+        // The meshes are modified in place, so that the entities don't have to be respawned.
+        let drawn = match (previous, mesh) {
+            (Some((entity, handle)), Some(mesh)) => {
+                meshes.insert(&handle, mesh);
+
+                Some((entity, handle))
+            },
+            (Some((entity, _handle)), None) => {
+                commands.entity(entity).despawn();
+
+                None
+            },
+            (None, Some(mesh)) => {
+                let handle = meshes.add(mesh);
+
+                let entity = commands
+                    .spawn((
+                        Mesh2d(handle.clone()),
+                        MeshMaterial2d(material.clone()),
+                        DrawerMesh,
+                    ))
+                    .id();
+
+                Some((entity, handle))
+            },
+            (None, None) => None,
+        };
+
+        match drawn_shapes.get_mut(idx) {
+            Some(drawn_shape) => *drawn_shape = (shape_revision, drawn),
+            None => drawn_shapes.push((shape_revision, drawn)),
+        }
     }
 }
 
-fn clear_screen(mut commands: Commands, entities: Query<Entity, With<DrawerMesh>>)
+/// Updates the entities of the texts or the stamps of a drawer, only the ones whose revision has changed since they were last displayed are respawned.
+fn update_sprites<T>(
+    commands: &mut Commands,
+    drawn_sprites: &mut Vec<(u64, Entity)>,
+    sprites: &[T],
+    revision: fn(&T) -> u64,
+    mut spawn: impl FnMut(&mut Commands, &T) -> Entity,
+)
 {
-    for entity in entities.iter() {
+    // Remove the sprites which no longer exist
+    for (_, entity) in drawn_sprites.drain(sprites.len().min(drawn_sprites.len())..) {
         commands.entity(entity).despawn();
     }
+
+    for (idx, sprite) in sprites.iter().enumerate() {
+        let sprite_revision = revision(sprite);
+
+        match drawn_sprites.get_mut(idx) {
+            Some((drawn_revision, _)) if *drawn_revision == sprite_revision => (),
+            Some(drawn_sprite) => {
+                commands.entity(drawn_sprite.1).despawn();
+
+                *drawn_sprite = (sprite_revision, spawn(commands, sprite));
+            },
+            None => drawn_sprites.push((sprite_revision, spawn(commands, sprite))),
+        }
+    }
+}
+
+/// Places a sprite at the position, rotated so that it is upright if the angle points upwards.
+fn sprite_transform(pos: Vec2, ang: &Angle, z: f32) -> Transform
+{
+    Transform::from_xyz(pos.x, pos.y, z).with_rotation(Quat::from_rotation_z(
+        Angle::from_degrees(ang.to_degrees() - 90.).to_radians(),
+    ))
+}
+
+/// Creates the sprite of a costume, if the costume is not set or it couldn't be loaded Ferris is displayed.
+/// The sprite is scaled so that its longer side is [`COSTUME_SIZE`] long.
+fn costume_sprite(
+    asset_server: &AssetServer,
+    costumes: &Costumes,
+    costume: Option<&str>,
+) -> Sprite
+{
+    let (image, size) = match costume.and_then(|name| costumes.handles.get(name)) {
+        Some((handle, size)) => (handle.clone(), *size),
+        None => (
+            asset_server.load("embedded://ferris_draw/../assets/ferris.png"),
+            Vec2::splat(COSTUME_SIZE),
+        ),
+    };
+
+    Sprite {
+        custom_size: Some(size * (COSTUME_SIZE / size.max_element())),
+        ..Sprite::from_image(image)
+    }
+}
+
+/// Decodes the project's images whenever the list of images is modified, so that they can be used as costumes.
+fn load_costumes(
+    mut costumes: ResMut<Costumes>,
+    mut images: ResMut<Assets<Image>>,
+    ui_state: Res<UiState>,
+)
+{
+    let image_library = ui_state.images.read();
+
+    if costumes.revision == Some(image_library.revision()) {
+        return;
+    }
+
+    for (_name, (handle, _size)) in costumes.handles.drain() {
+        images.remove(&handle);
+    }
+
+    for (name, project_image) in image_library.images() {
+        match project_image.decode() {
+            Ok(image) => {
+                let size = image.size_f32();
+
+                costumes
+                    .handles
+                    .insert(name.clone(), (images.add(image), size));
+            },
+            Err(err) => {
+                ui_state.toasts.lock().add(
+                    Toast::new()
+                        .kind(ToastKind::Error)
+                        .text(format!(r#"Failed to load image "{name}": {err}"#)),
+                );
+            },
+        }
+    }
+
+    costumes.revision = Some(image_library.revision());
 }
@@ -0,0 +1,351 @@
+use std::{mem::discriminant, ops::Deref, sync::Arc};
+
+use bevy::color::Srgba;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(target_family = "wasm"))]
+use mlua::{Function, IntoLua, Lua};
+
+#[cfg(not(target_family = "wasm"))]
+use crate::{
+    colors::{color_into_srgba, Palette},
+    LuaRuntime,
+};
+
+/// The value of a parameter declared by a script, this also determines how the parameter is displayed in the Script Manager.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ParameterValue
+{
+    /// A number, displayed as a slider if both of its bounds are set.
+    Number
+    {
+        value: f64,
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    /// A boolean, displayed as a checkbox.
+    Boolean(bool),
+    /// A color in sRGBA format, displayed as a color picker.
+    Color([f32; 4]),
+}
+
+impl ParameterValue
+{
+    /// Returns the color in hex format, the same format the scripts can set the colors in.
+    pub fn hex_color(color: [f32; 4]) -> String
+    {
+        let [red, green, blue, alpha] = color;
+
+        Srgba::new(red, green, blue, alpha).to_hex()
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl IntoLua for ParameterValue
+{
+    fn into_lua(self, lua: &Lua) -> mlua::Result<mlua::Value>
+    {
+        match self {
+            ParameterValue::Number { value, .. } => value.into_lua(lua),
+            ParameterValue::Boolean(value) => value.into_lua(lua),
+            ParameterValue::Color(color) => ParameterValue::hex_color(color).into_lua(lua),
+        }
+    }
+}
+
+/// A parameter declared by a script with `param`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScriptParameter
+{
+    pub name: String,
+    pub value: ParameterValue,
+
+    /// Whether the script has declared the parameter since it was last started.
+    #[serde(skip)]
+    declared: bool,
+}
+
+/// The parameters of a script, in the order the script has declared them.
+/// The values are kept between the runs of the script, and they are saved with the project.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ScriptParameters(Arc<RwLock<Vec<ScriptParameter>>>);
+
+impl Deref for ScriptParameters
+{
+    type Target = Arc<RwLock<Vec<ScriptParameter>>>;
+
+    fn deref(&self) -> &Self::Target
+    {
+        &self.0
+    }
+}
+
+impl PartialEq for ScriptParameters
+{
+    fn eq(&self, other: &Self) -> bool
+    {
+        Arc::ptr_eq(&self.0, &other.0) || *self.0.read() == *other.0.read()
+    }
+}
+
+impl ScriptParameters
+{
+    /// Declares a parameter, and returns its current value.
+    /// If the parameter already exists with the same type only its bounds are updated, otherwise it is reset to the default value.
+    pub fn declare(&self, name: &str, default: ParameterValue) -> ParameterValue
+    {
+        let mut parameters = self.write();
+
+        let Some(parameter) = parameters
+            .iter_mut()
+            .find(|parameter| parameter.name == name)
+        else {
+            parameters.push(ScriptParameter {
+                name: name.to_string(),
+                value: default,
+                declared: true,
+            });
+
+            return default;
+        };
+
+        parameter.declared = true;
+
+        match (&mut parameter.value, default) {
+            (
+                ParameterValue::Number { value, min, max },
+                ParameterValue::Number {
+                    min: new_min,
+                    max: new_max,
+                    ..
+                },
+            ) => {
+                *min = new_min;
+                *max = new_max;
+                *value = value.clamp(new_min.unwrap_or(f64::MIN), new_max.unwrap_or(f64::MAX));
+            },
+            (value, default) if discriminant(&*value) != discriminant(&default) => {
+                *value = default;
+            },
+            _ => (),
+        }
+
+        parameter.value
+    }
+
+    /// Copies the parameters, the copy can be declared without changing the values or the declarations of these parameters.
+    pub fn detached(&self) -> Self
+    {
+        Self(Arc::new(RwLock::new(self.read().clone())))
+    }
+
+    /// Marks every parameter as undeclared, this should be called every time the script is started.
+    pub fn start_declaring(&self)
+    {
+        for parameter in self.write().iter_mut() {
+            parameter.declared = false;
+        }
+    }
+
+    /// Removes the parameters the script hasn't declared since it was last started.
+    pub fn remove_undeclared(&self)
+    {
+        self.write().retain(|parameter| parameter.declared);
+    }
+}
+
+/// Creates the `param(name, default, min, max)` lua function, which declares a parameter of the script and returns its current value.
+/// The type of the parameter is decided by the default value, which can be a number, a boolean or a color (a hex color, a color of the palette or a CSS color name).
+#[cfg(not(target_family = "wasm"))]
+pub fn create_param_function(
+    lua_runtime: &LuaRuntime,
+    parameters: ScriptParameters,
+    palette: Arc<RwLock<Palette>>,
+) -> mlua::Result<Function>
+{
+    lua_runtime.create_function(
+        move |_, params: (String, mlua::Value, Option<f64>, Option<f64>)| {
+            let (name, default, min, max) = params;
+
+            let mut default = match default {
+                mlua::Value::Integer(value) => ParameterValue::Number {
+                    value: value as f64,
+                    min,
+                    max,
+                },
+                mlua::Value::Number(value) => ParameterValue::Number { value, min, max },
+                mlua::Value::Boolean(value) => ParameterValue::Boolean(value),
+                mlua::Value::String(text) => {
+                    let text = text.to_str()?.to_string();
+
+                    let Some(color) = palette.read().parse(&text)
+                    else {
+                        return Err(mlua::Error::RuntimeError(format!(
+                            r#"The default value of the parameter "{name}" is not a valid color: "{text}"."#
+                        )));
+                    };
+
+                    ParameterValue::Color(color_into_srgba(color))
+                },
+                value => {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        r#"The default value of the parameter "{name}" must be a number, a boolean or a color, not a {}."#,
+                        value.type_name()
+                    )));
+                },
+            };
+
+            if let ParameterValue::Number { value, .. } = &mut default {
+                if !value.is_finite()
+                    || min.is_some_and(|min| !min.is_finite())
+                    || max.is_some_and(|max| !max.is_finite())
+                {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        r#"The parameter "{name}" and its bounds must be finite numbers."#
+                    )));
+                }
+
+                if let (Some(min), Some(max)) = (min, max) {
+                    if min > max {
+                        return Err(mlua::Error::RuntimeError(format!(
+                            r#"The minimum of the parameter "{name}" is larger than its maximum."#
+                        )));
+                    }
+                }
+
+                *value = value.clamp(min.unwrap_or(f64::MIN), max.unwrap_or(f64::MAX));
+            }
+
+            Ok(parameters.declare(&name, default))
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::{ParameterValue, ScriptParameters};
+
+    fn number(value: f64, min: Option<f64>, max: Option<f64>) -> ParameterValue
+    {
+        ParameterValue::Number { value, min, max }
+    }
+
+    /// Sets the value of the parameter, like the Script Manager does when the user changes it.
+    fn set_value(parameters: &ScriptParameters, name: &str, value: ParameterValue)
+    {
+        parameters
+            .write()
+            .iter_mut()
+            .find(|parameter| parameter.name == name)
+            .unwrap()
+            .value = value;
+    }
+
+    #[test]
+    fn values_are_kept_between_runs()
+    {
+        let parameters = ScriptParameters::default();
+
+        assert_eq!(
+            parameters.declare("size", number(5., Some(0.), Some(10.))),
+            number(5., Some(0.), Some(10.))
+        );
+
+        set_value(&parameters, "size", number(7., Some(0.), Some(10.)));
+
+        parameters.start_declaring();
+
+        assert_eq!(
+            parameters.declare("size", number(5., Some(0.), Some(10.))),
+            number(7., Some(0.), Some(10.))
+        );
+
+        parameters.declare("color", ParameterValue::Color([1., 0., 0., 1.]));
+        set_value(&parameters, "color", ParameterValue::Color([0., 0., 1., 1.]));
+
+        assert_eq!(
+            parameters.declare("color", ParameterValue::Color([0., 1., 0., 1.])),
+            ParameterValue::Color([0., 0., 1., 1.])
+        );
+    }
+
+    #[test]
+    fn values_are_reset_if_the_type_changes()
+    {
+        let parameters = ScriptParameters::default();
+
+        parameters.declare("filled", number(1., None, None));
+
+        assert_eq!(
+            parameters.declare("filled", ParameterValue::Boolean(true)),
+            ParameterValue::Boolean(true)
+        );
+
+        set_value(&parameters, "filled", ParameterValue::Boolean(false));
+
+        assert_eq!(
+            parameters.declare("filled", ParameterValue::Color([1., 1., 1., 1.])),
+            ParameterValue::Color([1., 1., 1., 1.])
+        );
+        assert_eq!(parameters.read().len(), 1);
+    }
+
+    #[test]
+    fn values_are_clamped_to_the_new_bounds()
+    {
+        let parameters = ScriptParameters::default();
+
+        parameters.declare("size", number(5., Some(0.), Some(10.)));
+        set_value(&parameters, "size", number(8., Some(0.), Some(10.)));
+
+        assert_eq!(
+            parameters.declare("size", number(5., Some(0.), Some(6.))),
+            number(6., Some(0.), Some(6.))
+        );
+
+        // The bounds can also be removed, the value is kept as is then.
+        assert_eq!(
+            parameters.declare("size", number(5., None, None)),
+            number(6., None, None)
+        );
+
+        assert_eq!(
+            parameters.declare("size", number(5., Some(10.), None)),
+            number(10., Some(10.), None)
+        );
+    }
+
+    #[test]
+    fn undeclared_parameters_are_removed()
+    {
+        let parameters = ScriptParameters::default();
+
+        parameters.declare("size", number(5., None, None));
+        parameters.declare("filled", ParameterValue::Boolean(true));
+        parameters.declare("color", ParameterValue::Color([1., 1., 1., 1.]));
+
+        parameters.start_declaring();
+
+        parameters.declare("color", ParameterValue::Color([1., 1., 1., 1.]));
+        parameters.declare("size", number(5., None, None));
+
+        parameters.remove_undeclared();
+
+        // The parameters keep the order they were first declared in.
+        let names: Vec<String> = parameters
+            .read()
+            .iter()
+            .map(|parameter| parameter.name.clone())
+            .collect();
+
+        assert_eq!(names, ["size", "color"]);
+
+        // The parameters stay declared until the script is started again.
+        parameters.remove_undeclared();
+
+        assert_eq!(parameters.read().len(), 2);
+    }
+}
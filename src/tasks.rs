@@ -0,0 +1,907 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Waker},
+    time::Instant,
+};
+
+use bevy::{math::Vec2, prelude::Resource};
+use egui_toast::{Toast, ToastKind, Toasts};
+use mlua::{
+    Debug, Error, Function, HookTriggers, Lua, MultiValue, Table, Thread, UserData,
+    UserDataMethods, Variadic, VmState,
+};
+use parking_lot::{Mutex, RwLock};
+
+use crate::{
+    AnimationSpeed, Drawers, ExecutionLimits, FilledPolygonPoints, LuaRuntime, MAX_WAIT_SECONDS,
+};
+
+/// The amount of instructions the lua code executes between two checks of the [`ExecutionLimits`].
+const INSTRUCTIONS_PER_CHECK: u32 = 10_000;
+
+/// The lua functions which move the drawers along a path.
+/// When the drawers are animated these functions wait until the drawer has walked along its path.
+pub const WALKING_FUNCTIONS: &[&str] = &[
+    "forward", "go_to", "set_x", "set_y", "home", "arc", "circle", "ellipse", "curve_to",
+    "cubic_to", "spline",
+];
+
+/// Identifies what has started a [`ScriptTask`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskOrigin
+{
+    /// The code was entered in the command panel.
+    CommandLine,
+    /// The script with the given name was run from the script manager.
+    Script(String),
+}
+
+/// A piece of lua code, which is being executed over multiple frames.
+pub struct ScriptTask
+{
+    /// What has started the task.
+    pub origin: TaskOrigin,
+
+    /// Identifies the task, the ids are unique among the tasks of the [`ScriptTasks`].
+    id: u64,
+
+    /// The coroutine executing the code, the [`ExecutionLimits`] are installed on it every time before it is resumed.
+    /// This is missing if the code couldn't be loaded, the task then finishes with the error the first time it is polled.
+    thread: Option<Thread>,
+
+    /// The execution of the code, this is polled once every frame.
+    future: Pin<Box<dyn Future<Output = mlua::Result<()>> + Send>>,
+}
+
+/// The lua code currently being executed.
+/// The code runs as a coroutine, so that it can be paused while the drawers are walking, without freezing the app.
+#[derive(Resource, Default, Clone)]
+pub struct ScriptTasks
+{
+    tasks: Arc<Mutex<Vec<ScriptTask>>>,
+    next_id: Arc<AtomicU64>,
+
+    /// The id of the task being polled, the lua functions can only pause the code if they were called from a task.
+    polled_task: Arc<Mutex<Option<u64>>>,
+
+    /// The drawers which are walking along their paths, with the ids of the tasks which have moved them.
+    walks: Arc<Mutex<Vec<(u64, SharedWalk)>>>,
+}
+
+impl ScriptTasks
+{
+    /// Starts executing the code, the code is first run when the tasks are polled.
+    /// If an environment is given the code reads and sets its global variables in it, instead of the global table.
+    pub fn spawn(
+        &self,
+        lua_runtime: &LuaRuntime,
+        code: &str,
+        origin: TaskOrigin,
+        environment: Option<Table>,
+    )
+    {
+        let mut chunk = lua_runtime.load(code.to_string());
+
+        if let Some(environment) = environment {
+            chunk = chunk.set_environment(environment);
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+        let thread = chunk
+            .into_function()
+            .and_then(|function| lua_runtime.create_thread(function));
+
+        let task = match thread {
+            Ok(thread) => {
+                ScriptTask {
+                    origin,
+                    id,
+                    thread: Some(thread.clone()),
+                    future: Box::pin(thread.into_async::<()>(())),
+                }
+            },
+            Err(error) => {
+                ScriptTask {
+                    origin,
+                    id,
+                    thread: None,
+                    future: Box::pin(std::future::ready(Err(error))),
+                }
+            },
+        };
+
+        self.tasks.lock().push(task);
+    }
+
+    /// Runs every task until it is paused or it has finished, the [`ExecutionLimits`] apply to every task separately.
+    /// Returns the origins of the finished tasks, with the results of their execution.
+    pub fn poll(&self, lua_runtime: &LuaRuntime) -> Vec<(TaskOrigin, mlua::Result<()>)>
+    {
+        let mut context = Context::from_waker(Waker::noop());
+        let mut finished = vec![];
+
+        self.tasks.lock().retain_mut(|task| {
+            restart_execution_budget(lua_runtime);
+
+            *self.polled_task.lock() = Some(task.id);
+
+            if let Some(thread) = &task.thread {
+                install_execution_hook(lua_runtime, Some(thread));
+            }
+
+            match task.future.as_mut().poll(&mut context) {
+                Poll::Ready(result) => {
+                    finished.push((task.origin.clone(), result));
+
+                    false
+                },
+                Poll::Pending => true,
+            }
+        });
+
+        *self.polled_task.lock() = None;
+
+        // The hook was moved to the coroutines of the tasks, the rest of the lua code runs in the main state.
+        install_execution_hook(lua_runtime, None);
+
+        finished
+    }
+
+    /// Stops executing the tasks with the given origin, the code is dropped where it has last paused.
+    /// The drawers the tasks were walking are moved to the end of their paths at once, so that they aren't left in the middle of a line.
+    pub fn cancel(&self, origin: &TaskOrigin)
+    {
+        let mut cancelled = vec![];
+
+        self.tasks.lock().retain(|task| {
+            if task.origin == *origin {
+                cancelled.push(task.id);
+            }
+
+            task.origin != *origin
+        });
+
+        self.walks.lock().retain(|(task, walk)| {
+            if cancelled.contains(task) {
+                walk.lock().finish();
+            }
+
+            !cancelled.contains(task)
+        });
+    }
+
+    /// Returns whether the lua functions are being called from a task.
+    pub fn is_polling(&self) -> bool
+    {
+        self.polled_task.lock().is_some()
+    }
+
+    /// Registers the walk of a drawer, which was started by the task being polled.
+    /// The walk has to be removed with [`ScriptTasks::end_walk`] once the drawer has reached the end of the path.
+    fn start_walk(&self, walk: Walk) -> SharedWalk
+    {
+        let walk = Arc::new(Mutex::new(walk));
+
+        if let Some(task) = *self.polled_task.lock() {
+            self.walks.lock().push((task, walk.clone()));
+        }
+
+        walk
+    }
+
+    /// Removes a walk registered with [`ScriptTasks::start_walk`].
+    fn end_walk(&self, walk: &SharedWalk)
+    {
+        self.walks
+            .lock()
+            .retain(|(_, registered)| !Arc::ptr_eq(registered, walk));
+    }
+
+    /// Returns whether a task with the given origin is being executed.
+    pub fn is_running(&self, origin: &TaskOrigin) -> bool
+    {
+        self.tasks.lock().iter().any(|task| task.origin == *origin)
+    }
+}
+
+/// A future which is ready the second time it is polled.
+/// As the [`ScriptTasks`] are polled once every frame, awaiting this pauses the task until the next frame.
+#[derive(Default)]
+pub struct NextFrame(bool);
+
+impl Future for NextFrame
+{
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output>
+    {
+        if self.0 {
+            return Poll::Ready(());
+        }
+
+        self.0 = true;
+
+        cx.waker().wake_by_ref();
+
+        Poll::Pending
+    }
+}
+
+/// The part of a path a drawer hasn't walked along yet, see [`walk_path`].
+struct Walk
+{
+    drawers: Drawers,
+    id: String,
+    path: VecDeque<Vec2>,
+
+    /// The shapes filled while the path was collected, these are filled once the drawer has walked around them.
+    polygons: Vec<FilledPolygonPoints>,
+
+    /// The point the drawer has walked to in the middle of a line, this is replaced every frame.
+    midpoint: Option<Vec2>,
+}
+
+impl Walk
+{
+    /// Moves the drawer along the rest of the path at once, and fills the shapes.
+    fn finish(&mut self)
+    {
+        // The drawer could have been removed while it was walking.
+        let Some(mut drawer) = self.drawers.get_mut(&self.id)
+        else {
+            return;
+        };
+
+        if let Some(midpoint) = self.midpoint.take() {
+            drawer.untrace_point(midpoint);
+        }
+
+        drawer.trace_path(&self.path.drain(..).collect::<Vec<Vec2>>());
+
+        for polygon in self.polygons.drain(..) {
+            drawer.push_polygon(polygon);
+        }
+    }
+}
+
+/// A [`Walk`] shared between the walking drawer and the [`ScriptTasks`], so that the walk can be finished if its task is cancelled.
+type SharedWalk = Arc<Mutex<Walk>>;
+
+/// Moves the drawer along the path gradually, by the distance the [`AnimationSpeed`] allows every frame.
+/// The rest of the path is traced at once if the animation is turned off meanwhile.
+/// The point the drawer has walked to in the middle of a line is replaced every frame, so only the points of the path are kept in the drawings.
+async fn walk_path(walk: &Mutex<Walk>, animation_speed: &RwLock<AnimationSpeed>)
+{
+    let mut last_frame = Instant::now();
+
+    while !walk.lock().path.is_empty() {
+        NextFrame::default().await;
+
+        let mut walk = walk.lock();
+
+        let Some(units_per_second) = animation_speed.read().units_per_second()
+        else {
+            break;
+        };
+
+        let Walk {
+            drawers,
+            id,
+            path,
+            midpoint,
+            ..
+        } = &mut *walk;
+
+        let Some(mut drawer) = drawers.get_mut(id)
+        else {
+            return;
+        };
+
+        if let Some(midpoint) = midpoint.take() {
+            drawer.untrace_point(midpoint);
+        }
+
+        let mut distance = units_per_second * last_frame.elapsed().as_secs_f32();
+        let mut position = drawer.pos;
+        let mut walked = vec![];
+
+        last_frame = Instant::now();
+
+        while let Some(next_point) = path.front() {
+            let remaining = position.distance(*next_point);
+
+            if remaining > distance {
+                position = position.move_towards(*next_point, distance);
+                walked.push(position);
+                *midpoint = Some(position);
+
+                break;
+            }
+
+            distance -= remaining;
+            position = *next_point;
+            walked.push(position);
+
+            path.pop_front();
+        }
+
+        drawer.trace_path(&walked);
+    }
+
+    walk.lock().finish();
+}
+
+/// Replaces the [`WALKING_FUNCTIONS`] with functions which make the drawers walk along their paths, when the drawers are animated.
+/// The paths are only walked along if the function was called from a [`ScriptTask`], otherwise the drawer moves instantly.
+pub fn animate_walking_functions(
+    lua_runtime: &LuaRuntime,
+    drawers_handle: Drawers,
+    animation_speed: Arc<RwLock<AnimationSpeed>>,
+    script_tasks: ScriptTasks,
+)
+{
+    for name in WALKING_FUNCTIONS {
+        let function: Function = lua_runtime.globals().get(*name).unwrap();
+
+        let drawers_clone = drawers_handle.clone();
+        let animation_speed = animation_speed.clone();
+        let script_tasks = script_tasks.clone();
+
+        let walking_function = lua_runtime
+            .create_async_function(move |_, params: (String, Variadic<mlua::Value>)| {
+                let (id, args) = params;
+
+                let function = function.clone();
+                let drawers_clone = drawers_clone.clone();
+                let animation_speed = animation_speed.clone();
+                let script_tasks = script_tasks.clone();
+
+                let is_animated =
+                    script_tasks.is_polling() && animation_speed.read().units_per_second().is_some();
+
+                async move {
+                    // Collect the path of the drawer instead of moving it, so that it can walk along the path afterwards.
+                    if is_animated {
+                        if let Some(mut drawer) = drawers_clone.get_mut(&id) {
+                            drawer.queued_path = Some(vec![]);
+                        }
+                    }
+
+                    let result = function.call::<MultiValue>((id.clone(), args));
+
+                    // The path is taken even if the function has failed, so that the drawer doesn't keep collecting its paths.
+                    let queued = drawers_clone.get_mut(&id).and_then(|mut drawer| {
+                        let polygons = std::mem::take(&mut drawer.queued_polygons);
+
+                        drawer.queued_path.take().map(|path| (path, polygons))
+                    });
+
+                    let result = result?;
+
+                    if let Some((path, polygons)) = queued {
+                        let walk = script_tasks.start_walk(Walk {
+                            drawers: drawers_clone,
+                            id,
+                            path: path.into(),
+                            polygons,
+                            midpoint: None,
+                        });
+
+                        walk_path(&walk, &animation_speed).await;
+
+                        script_tasks.end_walk(&walk);
+                    }
+
+                    Ok(result)
+                }
+            })
+            .unwrap();
+
+        lua_runtime.globals().set(*name, walking_function).unwrap();
+    }
+}
+
+/// How long the lua code has been running since the app has last started or resumed it.
+/// This is stored in the lua runtime's app data.
+struct ExecutionBudget
+{
+    started: Instant,
+    instructions: u64,
+}
+
+/// Restarts measuring how long the lua code runs, this should be called every time before the app runs lua code.
+pub fn restart_execution_budget(lua_runtime: &LuaRuntime)
+{
+    lua_runtime.set_app_data(ExecutionBudget {
+        started: Instant::now(),
+        instructions: 0,
+    });
+}
+
+/// The callback which stops the lua code once it has exceeded the [`ExecutionLimits`], see [`limit_execution`].
+/// This is stored in the lua runtime's app data, so that it can be installed on the coroutines of the [`ScriptTasks`] too.
+#[derive(Clone)]
+struct ExecutionHook(Arc<HookCallback>);
+
+type HookCallback = dyn Fn(&Lua, Debug) -> mlua::Result<VmState> + Send + Sync;
+
+/// Installs the [`ExecutionHook`] on the coroutine, or on the main state if no coroutine is given.
+/// mlua only calls a hook on the thread it was installed on, and as LuaJIT shares the hook between every thread, it is removed entirely the first time another thread runs into it.
+/// So the hook has to be moved to every coroutine before it is resumed, and back to the main state afterwards.
+fn install_execution_hook(lua_runtime: &LuaRuntime, thread: Option<&Thread>)
+{
+    let Some(hook) = lua_runtime
+        .app_data_ref::<ExecutionHook>()
+        .map(|hook| hook.clone())
+    else {
+        return;
+    };
+
+    let triggers = HookTriggers::new().every_nth_instruction(INSTRUCTIONS_PER_CHECK);
+    let callback = move |lua: &Lua, debug: Debug| (hook.0)(lua, debug);
+
+    match thread {
+        Some(thread) => thread.set_hook(triggers, callback),
+        None => lua_runtime.set_hook(triggers, callback),
+    }
+}
+
+/// Stops the lua code with an error if it runs longer or draws more points than the [`ExecutionLimits`] allow, and limits the memory of the lua runtime.
+/// A warning is also displayed if the points have run out, as the script might not display its errors.
+pub fn limit_execution(
+    lua_runtime: &LuaRuntime,
+    drawers_handle: Drawers,
+    toasts_handle: Arc<Mutex<Toasts>>,
+    execution_limits: Arc<RwLock<ExecutionLimits>>,
+)
+{
+    restart_execution_budget(lua_runtime);
+
+    limit_memory(lua_runtime, execution_limits.read().max_memory_megabytes);
+
+    let hook = ExecutionHook(Arc::new(move |lua, _| {
+        let Some(mut budget) = lua.app_data_mut::<ExecutionBudget>()
+        else {
+            return Ok(VmState::Continue);
+        };
+
+        let limits = *execution_limits.read();
+
+        budget.instructions += INSTRUCTIONS_PER_CHECK as u64;
+
+        if limits.max_instructions != 0 && budget.instructions > limits.max_instructions {
+            return Err(Error::RuntimeError(format!(
+                "The script was stopped, because it has executed more than {} instructions without pausing. Check your loops, or raise the limit in the settings.",
+                limits.max_instructions
+            )));
+        }
+
+        if limits.max_seconds > 0. && budget.started.elapsed().as_secs_f32() > limits.max_seconds {
+            return Err(Error::RuntimeError(format!(
+                "The script was stopped, because it has been running for more than {} seconds without pausing. Check your loops, or raise the limit in the settings.",
+                limits.max_seconds
+            )));
+        }
+
+        if limits.max_points != 0 && drawers_handle.point_count() > limits.max_points {
+            return Err(warn(
+                &toasts_handle,
+                format!(
+                    "The script was stopped, because the drawings contain more than {} points. Wipe the canvas, or raise the limit in the settings.",
+                    limits.max_points
+                ),
+            ));
+        }
+
+        Ok(VmState::Continue)
+    }));
+
+    lua_runtime.set_app_data(hook);
+
+    install_execution_hook(lua_runtime, None);
+
+    // The hooks are not called from the code compiled by LuaJIT, so an endless loop would never be stopped.
+    // The jit library is removed afterwards, so that the scripts can't turn the compiler back on.
+    lua_runtime
+        .load("if jit then jit.off() end jit = nil")
+        .exec()
+        .unwrap();
+}
+
+/// Limits the memory the lua runtime can allocate, this has to be called every time [`ExecutionLimits::max_memory_megabytes`] is changed.
+/// The allocations past the limit fail with a "not enough memory" error, so even a single huge allocation can't run the computer out of memory.
+pub fn limit_memory(lua_runtime: &LuaRuntime, max_memory_megabytes: u64)
+{
+    // A limit of 0 disables the limit for mlua too.
+    let max_memory = (max_memory_megabytes as usize).saturating_mul(1024 * 1024);
+
+    lua_runtime
+        .set_memory_limit(max_memory)
+        .expect("The memory of the lua runtime can be limited, as it uses the allocator of mlua.");
+}
+
+/// Stops the drawing functions from adding points to the drawings, once they contain as many points as the [`ExecutionLimits`] allow.
+/// [`limit_execution`] only checks the points every so often, so the functions check the limit themselves before drawing.
+#[derive(Clone)]
+pub struct PointLimit
+{
+    drawers: Drawers,
+    toasts_handle: Arc<Mutex<Toasts>>,
+    execution_limits: Arc<RwLock<ExecutionLimits>>,
+}
+
+impl PointLimit
+{
+    pub fn new(
+        drawers: Drawers,
+        toasts_handle: Arc<Mutex<Toasts>>,
+        execution_limits: Arc<RwLock<ExecutionLimits>>,
+    ) -> Self
+    {
+        Self {
+            drawers,
+            toasts_handle,
+            execution_limits,
+        }
+    }
+
+    /// Returns an error if the drawings can't contain any more points.
+    pub fn check(&self) -> mlua::Result<()>
+    {
+        let max_points = self.execution_limits.read().max_points;
+
+        if max_points != 0 && self.drawers.point_count() >= max_points {
+            return Err(warn(
+                &self.toasts_handle,
+                format!(
+                    "The drawings can't contain more than {max_points} points. Wipe the canvas, or raise the limit in the settings."
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Displays the message as a warning and returns it as an error.
+fn warn(toasts_handle: &Mutex<Toasts>, message: String) -> Error
+{
+    toasts_handle
+        .lock()
+        .add(Toast::new().kind(ToastKind::Warning).text(message.clone()));
+
+    Error::RuntimeError(message)
+}
+
+/// A function a script has scheduled with `after` or `every`.
+struct ScriptTimer
+{
+    id: u64,
+    function: Function,
+
+    /// The time left until the function is called, in seconds.
+    remaining: f32,
+    /// The time between the calls of the function, if the function is called repeatedly.
+    interval: Option<f32>,
+    /// Whether the function should be called, see [`ScriptTimers::take_due`].
+    due: bool,
+}
+
+/// The functions a script has scheduled with `after` and `every`.
+/// The timers are advanced by the app every frame, until they are cancelled or the script is stopped.
+#[derive(Default, Clone)]
+pub struct ScriptTimers
+{
+    timers: Arc<Mutex<Vec<ScriptTimer>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl PartialEq for ScriptTimers
+{
+    fn eq(&self, other: &Self) -> bool
+    {
+        Arc::ptr_eq(&self.timers, &other.timers)
+    }
+}
+
+impl ScriptTimers
+{
+    /// Schedules the function to be called after the delay, and then every interval if it is set.
+    fn schedule(&self, function: Function, delay: f32, interval: Option<f32>) -> TimerHandle
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+        self.timers.lock().push(ScriptTimer {
+            id,
+            function,
+            remaining: delay,
+            interval,
+            due: false,
+        });
+
+        TimerHandle {
+            id,
+            timers: self.clone(),
+        }
+    }
+
+    /// Cancels every timer.
+    pub fn clear(&self)
+    {
+        self.timers.lock().clear();
+    }
+
+    /// Returns whether there aren't any timers scheduled.
+    pub fn is_empty(&self) -> bool
+    {
+        self.timers.lock().is_empty()
+    }
+
+    /// Advances the timers by the elapsed time, and marks the timers which are due.
+    /// The repeating timers are due at most once per call, the functions of the due timers are taken with [`ScriptTimers::take_due`].
+    pub fn advance(&self, elapsed: f32)
+    {
+        for timer in self.timers.lock().iter_mut() {
+            timer.remaining -= elapsed;
+
+            if timer.remaining > 0. {
+                continue;
+            }
+
+            timer.due = true;
+
+            // If the app has been lagging behind the timer isn't called repeatedly to catch up.
+            if let Some(interval) = timer.interval {
+                timer.remaining += interval;
+
+                if timer.remaining <= 0. {
+                    timer.remaining = interval;
+                }
+            }
+        }
+    }
+
+    /// Returns the function of the next due timer, the timers which don't repeat are removed once their function is taken.
+    /// The functions are taken one by one, so a timer cancelled by the function of another timer is never called.
+    pub fn take_due(&self) -> Option<Function>
+    {
+        let mut timers = self.timers.lock();
+
+        let index = timers.iter().position(|timer| timer.due)?;
+        let timer = &mut timers[index];
+
+        timer.due = false;
+
+        let function = timer.function.clone();
+
+        if timer.interval.is_none() {
+            timers.remove(index);
+        }
+
+        Some(function)
+    }
+}
+
+/// The handle returned by `after` and `every`, the timer can be cancelled by calling its `cancel` method.
+pub struct TimerHandle
+{
+    id: u64,
+    timers: ScriptTimers,
+}
+
+impl UserData for TimerHandle
+{
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M)
+    {
+        methods.add_method("cancel", |_, handle, ()| {
+            handle
+                .timers
+                .timers
+                .lock()
+                .retain(|timer| timer.id != handle.id);
+
+            Ok(())
+        });
+    }
+}
+
+/// Checks the time given to `after` or `every`, and clamps it between 0 and [`MAX_WAIT_SECONDS`].
+fn timer_seconds(seconds: f32) -> mlua::Result<f32>
+{
+    if seconds.is_nan() {
+        return Err(Error::RuntimeError(
+            "The time of a timer can't be NaN.".to_string(),
+        ));
+    }
+
+    Ok(seconds.clamp(0., MAX_WAIT_SECONDS))
+}
+
+/// Creates the `after(seconds, function)` and `every(seconds, function)` lua functions of a script.
+/// `after` calls the function once after the given time, `every` calls it repeatedly with the given time between the calls.
+pub fn create_timer_functions(
+    lua_runtime: &LuaRuntime,
+    timers: ScriptTimers,
+) -> mlua::Result<(Function, Function)>
+{
+    let timers_handle = timers.clone();
+
+    let after = lua_runtime.create_function(move |_, params: (f32, Function)| {
+        let (seconds, function) = params;
+
+        Ok(timers_handle.schedule(function, timer_seconds(seconds)?, None))
+    })?;
+
+    let every = lua_runtime.create_function(move |_, params: (f32, Function)| {
+        let (seconds, function) = params;
+        let seconds = timer_seconds(seconds)?;
+
+        Ok(timers.schedule(function, seconds, Some(seconds)))
+    })?;
+
+    Ok((after, every))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::sync::Arc;
+
+    use egui_toast::Toasts;
+    use mlua::{Function, Lua};
+    use parking_lot::{Mutex, RwLock};
+
+    use super::{limit_execution, ScriptTasks, ScriptTimers, TaskOrigin};
+    use crate::{Drawers, ExecutionLimits, LuaRuntime};
+
+    /// Creates a function which returns the number, so that the functions of the timers can be told apart.
+    fn numbered_function(lua: &Lua, number: u32) -> Function
+    {
+        lua.create_function(move |_, ()| Ok(number)).unwrap()
+    }
+
+    /// Takes the functions of the due timers, and returns the numbers returned by them.
+    fn call_due(timers: &ScriptTimers) -> Vec<u32>
+    {
+        std::iter::from_fn(|| timers.take_due())
+            .map(|function| function.call::<u32>(()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn only_repeating_timers_are_kept()
+    {
+        let lua = Lua::new();
+        let timers = ScriptTimers::default();
+
+        timers.schedule(numbered_function(&lua, 1), 1., None);
+        timers.schedule(numbered_function(&lua, 2), 1., Some(1.));
+
+        timers.advance(0.5);
+        assert!(call_due(&timers).is_empty());
+
+        timers.advance(0.5);
+        assert_eq!(call_due(&timers), vec![1, 2]);
+
+        timers.advance(1.);
+        assert_eq!(call_due(&timers), vec![2]);
+        assert!(!timers.is_empty());
+
+        timers.clear();
+        assert!(timers.is_empty());
+    }
+
+    #[test]
+    fn repeating_timers_are_due_once_per_advance()
+    {
+        let lua = Lua::new();
+        let timers = ScriptTimers::default();
+
+        timers.schedule(numbered_function(&lua, 1), 0.25, Some(0.25));
+        timers.schedule(numbered_function(&lua, 2), 5., None);
+
+        timers.advance(1.);
+        assert_eq!(call_due(&timers), vec![1]);
+
+        // The timer doesn't catch up with the missed intervals, it's due again after a full interval.
+        timers.advance(0.125);
+        assert!(call_due(&timers).is_empty());
+
+        timers.advance(0.125);
+        assert_eq!(call_due(&timers), vec![1]);
+
+        timers.advance(10.);
+        assert_eq!(call_due(&timers), vec![1, 2]);
+    }
+
+    #[test]
+    fn cancelled_timers_are_not_called()
+    {
+        let lua = Lua::new();
+        let timers = ScriptTimers::default();
+
+        let cancel_second = lua
+            .load("return function() second:cancel() return 1 end")
+            .eval::<Function>()
+            .unwrap();
+
+        timers.schedule(cancel_second, 1., None);
+
+        let second = timers.schedule(numbered_function(&lua, 2), 1., Some(1.));
+
+        lua.globals().set("second", second).unwrap();
+
+        timers.advance(1.);
+        assert_eq!(call_due(&timers), vec![1]);
+        assert!(timers.is_empty());
+    }
+
+    /// Creates a lua runtime with the execution limits installed, which stops the code after a million instructions.
+    fn limited_lua_runtime() -> LuaRuntime
+    {
+        let lua_runtime = LuaRuntime::default();
+
+        limit_execution(
+            &lua_runtime,
+            Drawers::new(),
+            Arc::new(Mutex::new(Toasts::default())),
+            Arc::new(RwLock::new(ExecutionLimits {
+                max_seconds: 0.,
+                max_instructions: 1_000_000,
+                ..Default::default()
+            })),
+        );
+
+        lua_runtime
+    }
+
+    #[test]
+    fn endless_tasks_are_stopped()
+    {
+        let lua_runtime = limited_lua_runtime();
+        let script_tasks = ScriptTasks::default();
+
+        script_tasks.spawn(
+            &lua_runtime,
+            "while true do end",
+            TaskOrigin::CommandLine,
+            None,
+        );
+
+        let finished = script_tasks.poll(&lua_runtime);
+
+        assert_eq!(finished.len(), 1);
+        assert!(finished[0]
+            .1
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains("instructions"));
+        assert!(!script_tasks.is_running(&TaskOrigin::CommandLine));
+
+        // The hook is moved back to the main state after the tasks were polled.
+        let error = lua_runtime.load("while true do end").exec().unwrap_err();
+
+        assert!(error.to_string().contains("instructions"));
+    }
+
+    #[test]
+    fn invalid_tasks_finish_with_the_error()
+    {
+        let lua_runtime = limited_lua_runtime();
+        let script_tasks = ScriptTasks::default();
+
+        script_tasks.spawn(&lua_runtime, "end", TaskOrigin::CommandLine, None);
+
+        let finished = script_tasks.poll(&lua_runtime);
+
+        assert!(matches!(finished[..], [(TaskOrigin::CommandLine, Err(_))]));
+    }
+}
@@ -1,23 +1,38 @@
 use bevy::prelude::{Res, ResMut};
 use bevy_egui::{
-    egui::{self, vec2, Color32, Key, Pos2, RichText, ScrollArea, TextEdit, UiBuilder, Window},
+    egui::{
+        self, vec2, Color32, DragValue, Key, Pos2, RichText, ScrollArea, Slider, TextEdit,
+        UiBuilder, Window,
+    },
     EguiContexts,
 };
 use chrono::Local;
-use dashmap::DashMap;
 use egui_commonmark::{commonmark_str, CommonMarkCache};
-use fragile::Fragile;
 use miniz_oxide::{deflate::CompressionLevel, inflate::decompress_to_vec};
 
 #[cfg(not(target_family = "wasm"))]
-use mlua::{Function, IntoLua};
+use mlua::{Function, IntoLua, IntoLuaMulti, Table};
 
+#[cfg(target_family = "wasm")]
 use piccolo::Executor;
 use serde::Deserialize;
+#[cfg(not(target_family = "wasm"))]
+use bevy::{
+    input::{mouse::MouseButton, ButtonInput},
+    math::Vec2,
+    prelude::{Camera, Camera2d, GlobalTransform, Query, With},
+    time::Time,
+    window::PrimaryWindow,
+};
+#[cfg(not(target_family = "wasm"))]
+use std::collections::HashMap;
+#[cfg(target_family = "wasm")]
+use std::collections::HashSet;
+#[cfg(not(target_family = "wasm"))]
+use std::path::Path;
 use std::{
-    collections::{HashMap, HashSet, VecDeque},
+    collections::VecDeque,
     fs,
-    marker::PhantomData,
     path::PathBuf,
     sync::Arc,
 };
@@ -25,7 +40,20 @@ use strum::IntoEnumIterator;
 
 use parking_lot::{Mutex, RwLock};
 
-use crate::{LuaRuntime, SetLenBuffer};
+use crate::{
+    colors::Palette,
+    parameters::ScriptParameters,
+    AnimationSpeed, ExecutionLimits, LuaRuntime, SetLenBuffer,
+};
+
+#[cfg(not(target_family = "wasm"))]
+use crate::{
+    parameters::{create_param_function, ParameterValue},
+    tasks::{
+        create_timer_functions, limit_memory, restart_execution_budget, ScriptTasks, ScriptTimers,
+        TaskOrigin,
+    },
+};
 
 #[cfg(target_family = "wasm")]
 use crate::{Angle, Drawer, FilledPolygonPoints, LineStrip};
@@ -38,8 +66,8 @@ use bevy::{
 use piccolo::{Function, Value};
 
 use crate::{
-    CallbackType, DemoBuffer, DemoBufferState, DemoInstance, DemoStep, Drawers, ScriptLinePrompts,
-    DEMO_FILE_EXTENSION, PROJECT_FILE_EXTENSION,
+    CallbackType, DemoBuffer, DemoBufferState, DemoInstance, DemoStep, Drawers, DrawersSnapshot, ImageLibrary,
+    ProjectImage, ScriptLinePrompts, DEMO_FILE_EXTENSION, PROJECT_FILE_EXTENSION,
 };
 use base64::{prelude::BASE64_STANDARD, Engine as _};
 use bevy::prelude::Resource;
@@ -117,6 +145,30 @@ pub struct UiState
 
     /// The demos' rename text buffer.
     pub demo_rename_text_buffer: Arc<Mutex<String>>,
+
+    /// The images of the project, which can be used as the drawers' costumes.
+    pub images: Arc<RwLock<ImageLibrary>>,
+
+    /// The named colors defined by the project's scripts.
+    pub palette: Arc<RwLock<Palette>>,
+
+    /// How fast the drawers walk along the lines they draw.
+    pub animation_speed: Arc<RwLock<AnimationSpeed>>,
+
+    /// The limits of how long the scripts can run at once.
+    pub execution_limits: Arc<RwLock<ExecutionLimits>>,
+
+    /// Whether the project was trusted when it was saved, the user is asked to trust it again when it is opened.
+    pub requests_trust: bool,
+
+    /// Whether the project's scripts can access the files of the computer, see [`LuaRuntime::set_trusted`].
+    /// This is never loaded from a project, only the user can trust a project.
+    #[serde(skip)]
+    pub trusted: bool,
+
+    /// Whether the window asking the user to trust the opened project is displayed.
+    #[serde(skip)]
+    pub trust_prompt: bool,
 }
 
 impl Default for UiState
@@ -132,6 +184,7 @@ impl Default for UiState
                     tiles.insert_pane(ManagerPane::EntityManager),
                     tiles.insert_pane(ManagerPane::ScriptManager),
                     tiles.insert_pane(ManagerPane::DemoManager),
+                    tiles.insert_pane(ManagerPane::ImageManager),
                     tiles.insert_pane(ManagerPane::RubbishBin),
                 ];
 
@@ -151,6 +204,13 @@ impl Default for UiState
             demo_buffer: DemoBuffer::new(vec![]),
             scripts: Arc::new(Mutex::new(vec![])),
             demo_rename_text_buffer: Arc::new(Mutex::new(String::new())),
+            images: Arc::new(RwLock::new(ImageLibrary::default())),
+            palette: Arc::new(RwLock::new(Palette::default())),
+            animation_speed: Arc::new(RwLock::new(AnimationSpeed::default())),
+            execution_limits: Arc::new(RwLock::new(ExecutionLimits::default())),
+            requests_trust: false,
+            trusted: false,
+            trust_prompt: false,
         }
     }
 }
@@ -166,6 +226,8 @@ pub enum ManagerPane
     EntityManager,
     /// Demo manager tab.
     DemoManager,
+    /// Image manager tab.
+    ImageManager,
     /// Rubbish bin tab.
     RubbishBin,
 }
@@ -175,6 +237,7 @@ pub enum RubbishBinItem
 {
     Script(ScriptInstance),
     Demo(DemoInstance),
+    Image(String, ProjectImage),
 }
 
 /// The manager panel's inner behavior, the data it contains, this can be used to share data over to the tabs from the main ui.
@@ -212,6 +275,17 @@ pub struct ManagerBehavior
 
     /// This is where the users pastes the contents of their clipboard in order to import it.
     import_from_clipboard_buffer: Arc<Mutex<String>>,
+
+    /// The images of the project, which can be used as the drawers' costumes.
+    images: Arc<RwLock<ImageLibrary>>,
+
+    /// The scripts are run as tasks, so that they can be executed over multiple frames.
+    #[cfg(not(target_family = "wasm"))]
+    script_tasks: ScriptTasks,
+
+    /// The named colors of the project, the parameters of the scripts can default to them.
+    #[cfg(not(target_family = "wasm"))]
+    palette: Arc<RwLock<Palette>>,
 }
 
 /// A [`ScriptInstance`] holds information about one script.
@@ -237,6 +311,25 @@ pub struct ScriptInstance
     #[serde(skip)]
     #[cfg(target_family = "wasm")]
     pub callbacks: HashSet<CallbackType>,
+
+    /// The table the script stores its global variables and callbacks in, so that the scripts don't overwrite each other's callbacks.
+    /// This gets created every script start.
+    #[serde(skip)]
+    #[cfg(not(target_family = "wasm"))]
+    pub environment: Option<Table>,
+
+    /// The functions the script has scheduled with `after` and `every`.
+    #[serde(skip)]
+    #[cfg(not(target_family = "wasm"))]
+    pub timers: ScriptTimers,
+
+    /// The drawers as they were before the script has started, so that the canvas can be rolled back when the script is stopped.
+    #[serde(skip)]
+    pub canvas_snapshot: Option<DrawersSnapshot>,
+
+    /// The parameters the script has declared with `param`.
+    #[serde(default)]
+    pub parameters: ScriptParameters,
 }
 
 impl ScriptInstance
@@ -254,8 +347,239 @@ impl ScriptInstance
 
             #[cfg(target_family = "wasm")]
             callbacks: HashSet::new(),
+
+            #[cfg(not(target_family = "wasm"))]
+            environment: None,
+
+            #[cfg(not(target_family = "wasm"))]
+            timers: ScriptTimers::default(),
+
+            canvas_snapshot: None,
+
+            parameters: ScriptParameters::default(),
         }
     }
+
+    /// Marks the script as not running, and forgets the state the canvas was in before the script has started.
+    /// This must be used instead of setting `is_running`, so that the snapshot of the canvas isn't kept around.
+    pub fn mark_stopped(&mut self)
+    {
+        self.is_running = false;
+        self.canvas_snapshot = None;
+    }
+
+    /// Stops the script, its code stops executing and its callbacks and global variables are removed.
+    /// If `restore_canvas` is true the whole canvas is rolled back to the state it was in before the script has started,
+    /// this also undoes what the other scripts and the Command Panel have drawn since.
+    pub fn stop(
+        &mut self,
+        #[cfg(not(target_family = "wasm"))] script_tasks: &ScriptTasks,
+        drawers: &Drawers,
+        restore_canvas: bool,
+    )
+    {
+        self.is_running = false;
+        self.callbacks.clear();
+
+        #[cfg(not(target_family = "wasm"))]
+        {
+            script_tasks.cancel(&TaskOrigin::Script(self.name.clone()));
+
+            self.timers.clear();
+            self.environment = None;
+        }
+
+        if let Some(canvas_snapshot) = self.canvas_snapshot.take() {
+            if restore_canvas {
+                drawers.restore(canvas_snapshot);
+            }
+        }
+    }
+
+    /// Creates a new environment for the script, and forgets the callbacks of its previous run.
+    /// The environment falls back to the global table, so the script can still use the functions of the app and the variables set in the Command Panel.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn create_environment(
+        &mut self,
+        lua_runtime: &LuaRuntime,
+        palette: Arc<RwLock<Palette>>,
+    ) -> mlua::Result<Table>
+    {
+        let environment = create_fallback_environment(lua_runtime)?;
+
+        // The parameters are declared by the script, so that the script can only change its own parameters.
+        environment.set(
+            "param",
+            create_param_function(lua_runtime, self.parameters.clone(), palette)?,
+        )?;
+
+        let (after, every) = create_timer_functions(lua_runtime, self.timers.clone())?;
+
+        environment.set("after", after)?;
+        environment.set("every", every)?;
+
+        self.parameters.start_declaring();
+        self.timers.clear();
+        self.callbacks.clear();
+        self.environment = Some(environment.clone());
+
+        Ok(environment)
+    }
+
+    /// Creates an environment for recording the script into a demo.
+    /// This doesn't touch the environment of the script's runs, so the recording doesn't define any callbacks and the global table isn't clobbered.
+    /// `param` returns the current values of the parameters without declaring them, and the timers are never called since the demos only record what the script draws when it is run.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn create_demo_environment(
+        &self,
+        lua_runtime: &LuaRuntime,
+        palette: Arc<RwLock<Palette>>,
+    ) -> mlua::Result<Table>
+    {
+        let environment = create_fallback_environment(lua_runtime)?;
+
+        environment.set(
+            "param",
+            create_param_function(lua_runtime, self.parameters.detached(), palette)?,
+        )?;
+
+        let (after, every) = create_timer_functions(lua_runtime, ScriptTimers::default())?;
+
+        environment.set("after", after)?;
+        environment.set("every", every)?;
+
+        Ok(environment)
+    }
+
+    /// Returns the callback the script has defined, if it has defined it.
+    /// The callback is looked up in the script's environment, so the callbacks can be called while the script's code is still running, ie. waiting in a loop.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn callback(&self, callback_type: CallbackType) -> Option<Function>
+    {
+        self.environment
+            .as_ref()?
+            .raw_get::<Function>(callback_type.to_string())
+            .ok()
+    }
+
+    /// Collects the callbacks the script has defined, this should be called after the script has been executed.
+    /// The script is stopped if it doesn't have any callbacks or timers, since nothing is getting called by the app at runtime.
+    pub fn collect_callbacks(
+        &mut self,
+        #[cfg(target_family = "wasm")] lua_runtime: &LuaRuntime,
+    )
+    {
+        #[cfg(not(target_family = "wasm"))]
+        self.parameters.remove_undeclared();
+
+        for callback_type in CallbackType::iter() {
+            #[cfg(not(target_family = "wasm"))]
+            if let Some(function) = self.callback(callback_type) {
+                self.callbacks.insert(callback_type, function);
+            }
+
+            #[cfg(target_family = "wasm")]
+            {
+                lua_runtime.get().lock().enter(|ctx| {
+                    let function = ctx.get_global(callback_type.to_string());
+
+                    // If we could get the global function from the callback's name we can store the callback type in order to know which of the callbacks are available in the script.
+                    if let Value::Function(function) = function {
+                        self.callbacks.insert(callback_type);
+                    }
+                });
+            }
+        }
+
+        #[cfg(not(target_family = "wasm"))]
+        if self.callbacks.is_empty() && self.timers.is_empty() {
+            self.mark_stopped();
+        }
+
+        #[cfg(target_family = "wasm")]
+        if self.callbacks.is_empty() {
+            self.mark_stopped();
+        }
+    }
+
+    /// Calls the script's callback with the arguments, if the script is running and it has defined the callback.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn call_callback(
+        &self,
+        lua_runtime: &LuaRuntime,
+        callback_type: CallbackType,
+        args: impl IntoLuaMulti,
+    ) -> mlua::Result<()>
+    {
+        if !self.is_running {
+            return Ok(());
+        }
+
+        let Some(function) = self.callback(callback_type)
+        else {
+            return Ok(());
+        };
+
+        restart_execution_budget(lua_runtime);
+
+        function.call::<()>(args)
+    }
+}
+
+/// Creates an empty environment which falls back to the global table, the global variables set in it don't leak into the global table.
+#[cfg(not(target_family = "wasm"))]
+fn create_fallback_environment(lua_runtime: &LuaRuntime) -> mlua::Result<Table>
+{
+    let environment = lua_runtime.create_table()?;
+    let metatable = lua_runtime.create_table()?;
+
+    metatable.set("__index", lua_runtime.globals())?;
+    environment.set_metatable(Some(metatable));
+
+    Ok(environment)
+}
+
+/// Displays the parameters of a script, a slider is displayed for the numbers with both bounds set, a checkbox for the booleans and a color picker for the colors.
+/// Returns the parameters which were changed by the user.
+#[cfg(not(target_family = "wasm"))]
+fn script_parameters_ui(ui: &mut egui::Ui, parameters: &ScriptParameters) -> Vec<(String, ParameterValue)>
+{
+    let mut changed_parameters = vec![];
+
+    for parameter in parameters.write().iter_mut() {
+        let changed = ui
+            .horizontal(|ui| {
+                match &mut parameter.value {
+                    ParameterValue::Number {
+                        value,
+                        min: Some(min),
+                        max: Some(max),
+                    } => ui.add(Slider::new(value, *min..=*max).text(&parameter.name)),
+                    ParameterValue::Number { value, min, max } => {
+                        ui.label(&parameter.name);
+
+                        ui.add(
+                            DragValue::new(value)
+                                .range(min.unwrap_or(f64::MIN)..=max.unwrap_or(f64::MAX)),
+                        )
+                    },
+                    ParameterValue::Boolean(value) => ui.checkbox(value, &parameter.name),
+                    ParameterValue::Color(color) => {
+                        ui.label(&parameter.name);
+
+                        ui.color_edit_button_rgba_unmultiplied(color)
+                    },
+                }
+                .changed()
+            })
+            .inner;
+
+        if changed {
+            changed_parameters.push((parameter.name.clone(), parameter.value));
+        }
+    }
+
+    changed_parameters
 }
 
 /// Implement tiles for the ManagerBehavior so that it can be dsiplayed.
@@ -416,53 +740,74 @@ impl egui_tiles::Behavior<ManagerPane> for ManagerBehavior
                                             false => {
                                                 if ui.button("Run").clicked() {
                                                     script_instance.is_running = true;
+                                                    script_instance.canvas_snapshot = Some(self.drawers.snapshot());
+
+                                                    // Run the script as a task, so that the drawers can be animated.
+                                                    // The script's callbacks are called as soon as they are defined, `main_ui` checks whether the script has any when the task has finished.
+                                                    #[cfg(not(target_family = "wasm"))]
+                                                    match script_instance.create_environment(&self.lua_runtime, self.palette.clone()) {
+                                                        Ok(environment) => self.script_tasks.spawn(
+                                                            &self.lua_runtime,
+                                                            &script_instance.script,
+                                                            TaskOrigin::Script(script_instance.name.clone()),
+                                                            Some(environment),
+                                                        ),
+                                                        Err(err) => {
+                                                            self.toasts.lock().add(
+                                                                Toast::new()
+                                                                    .kind(egui_toast::ToastKind::Error)
+                                                                    .text(err.to_string()),
+                                                            );
 
-                                                    // Run the script
-                                                    // Pattern match an error and display it as a notification
-                                                    if let Err(err) = self
-                                                    .lua_runtime
-                                                    // Load the script as a string into the lua runtime
-                                                    .execute_code(&script_instance.script)
-                                                    {
-                                                        // Add the error into the toasts if it returned an error
-                                                        self.toasts.lock().add(
-                                                            Toast::new()
-                                                                .kind(egui_toast::ToastKind::Error)
-                                                                .text(err.to_string()),
-                                                        );
-
-                                                        script_instance.is_running = false;
-                                                        return;
-                                                    };
-
-                                                    for callback_type in CallbackType::iter() {
-                                                        #[cfg(not(target_family = "wasm"))]
-                                                        if let Ok(function) = self.lua_runtime.globals().get::<Function>(callback_type.to_string()) {
-                                                            script_instance.callbacks.insert(callback_type, function);
-                                                        }
+                                                            script_instance.mark_stopped();
+                                                        },
+                                                    }
 
-                                                        #[cfg(target_family = "wasm")]
+                                                    #[cfg(target_family = "wasm")]
+                                                    {
+                                                        // Run the script
+                                                        // Pattern match an error and display it as a notification
+                                                        if let Err(err) = self
+                                                        .lua_runtime
+                                                        // Load the script as a string into the lua runtime
+                                                        .execute_code(&script_instance.script)
                                                         {
-                                                            self.lua_runtime.get().lock().enter(|ctx| {
-                                                                let function = ctx.get_global(callback_type.to_string());
-
-                                                                // If we could get the global function from the callback's name we can store the callback type in order to know which of the callbacks are available in the script.
-                                                                if let Value::Function(function) = function {
-                                                                    script_instance.callbacks.insert(callback_type);
-                                                                }
-                                                            });
-                                                        }
-                                                    }
+                                                            // Add the error into the toasts if it returned an error
+                                                            self.toasts.lock().add(
+                                                                Toast::new()
+                                                                    .kind(egui_toast::ToastKind::Error)
+                                                                    .text(err.to_string()),
+                                                            );
+
+                                                            script_instance.mark_stopped();
+                                                            return;
+                                                        };
 
-                                                    // If there were no callbacks we can reset the state since nothing is getting called by the app at runtime
-                                                    if script_instance.callbacks.is_empty() {
-                                                        script_instance.is_running = false;
+                                                        script_instance.collect_callbacks(&self.lua_runtime);
                                                     }
                                                 }
                                             },
                                             true => {
                                                 if ui.button("Stop").clicked() {
-                                                    script_instance.is_running = false;
+                                                    script_instance.stop(
+                                                        #[cfg(not(target_family = "wasm"))]
+                                                        &self.script_tasks,
+                                                        &self.drawers,
+                                                        false,
+                                                    );
+                                                }
+
+                                                if ui
+                                                    .button("Stop and roll back canvas")
+                                                    .on_hover_text("Stops the script, and rolls the whole canvas back to the state it was in before the script has started. This also undoes what the other scripts and the Command Panel have drawn since.")
+                                                    .clicked()
+                                                {
+                                                    script_instance.stop(
+                                                        #[cfg(not(target_family = "wasm"))]
+                                                        &self.script_tasks,
+                                                        &self.drawers,
+                                                        true,
+                                                    );
                                                 }
                                             },
                                         }
@@ -524,6 +869,14 @@ impl egui_tiles::Behavior<ManagerPane> for ManagerBehavior
                                             // Flag the script as to be deleted
                                             should_keep = false;
 
+                                            // Stop the script, so that it doesn't keep running after being deleted
+                                            script_instance.stop(
+                                                #[cfg(not(target_family = "wasm"))]
+                                                &self.script_tasks,
+                                                &self.drawers,
+                                                false,
+                                            );
+
                                             //Insert the script into the rubbish bin
                                             self.rubbish_bin.lock().push(RubbishBinItem::Script(
                                                 script_instance.clone(),
@@ -587,8 +940,7 @@ impl egui_tiles::Behavior<ManagerPane> for ManagerBehavior
                                         // Add the Create demo button
                                         if ui.button("Create Demo").clicked() {
                                             //Store current drawers and canvas
-                                            let current_drawer_canvas =
-                                                Drawers(Arc::new(DashMap::clone(&self.drawers.0)));
+                                            let current_drawer_canvas = self.drawers.snapshot();
 
                                             //Clear the canvas, so that the demo creator has a clear canvas
                                             self.drawers.clear();
@@ -598,11 +950,23 @@ impl egui_tiles::Behavior<ManagerPane> for ManagerBehavior
 
                                             //Run lua script
                                             // If the DemoBuffer is in the [`Record`] state the lua runtime will automaticly load the called functions (created by the applications) into the demo buffer with their arguments
-                                            match self
+                                            // The script is run in its own environment, so that its global variables and callbacks don't leak into the global table.
+                                            #[cfg(not(target_family = "wasm"))]
+                                            let result = script_instance
+                                                .create_demo_environment(&self.lua_runtime, self.palette.clone())
+                                                .map_err(anyhow::Error::from)
+                                                .and_then(|environment| {
+                                                    self.lua_runtime
+                                                        .execute_code_in(&script_instance.script, environment)
+                                                });
+
+                                            #[cfg(target_family = "wasm")]
+                                            let result = self
                                                 .lua_runtime
                                                 // Load the script as a string
-                                                .execute_code(&script_instance.script)
-                                            {
+                                                .execute_code(&script_instance.script);
+
+                                            match result {
                                                 // The script has finished executing
                                                 Ok(_output) => {
                                                     // Get the demo's steps, while draining it from the original buffer
@@ -640,23 +1004,44 @@ impl egui_tiles::Behavior<ManagerPane> for ManagerBehavior
                                                     );
 
                                                     //Reset script state
-                                                    script_instance.is_running = false;
+                                                    script_instance.mark_stopped();
                                                 },
                                             }
 
                                             //Reset Demo buffer state
                                             self.demo_buffer.set_state(DemoBufferState::None);
 
-                                            // Clear up anything created by the demo
-                                            self.drawers.clear();
-
-                                            //Load back the state
-                                            self.drawers.clone_from(&current_drawer_canvas);
+                                            // Clear up anything created by the demo, and load back the state
+                                            self.drawers.restore(current_drawer_canvas);
                                         }
                                     });
                                 });
                             });
-                            
+
+                            // Display the parameters declared by the script, and notify the script if they were changed
+                            #[cfg(not(target_family = "wasm"))]
+                            ui.push_id((script_instance.name.clone(), "parameters"), |ui| {
+                                let changed_parameters = script_parameters_ui(ui, &script_instance.parameters);
+
+                                for (name, value) in changed_parameters {
+                                    if let Err(err) = script_instance.call_callback(
+                                        &self.lua_runtime,
+                                        CallbackType::OnParameterChange,
+                                        (name, value),
+                                    ) {
+                                        self.toasts.lock().add(
+                                            Toast::new()
+                                                .kind(egui_toast::ToastKind::Error)
+                                                .text(err.to_string()),
+                                        );
+
+                                        script_instance.stop(&self.script_tasks, &self.drawers, false);
+
+                                        break;
+                                    }
+                                }
+                            });
+
                             // Increment script_idx
                             script_idx += 1;
 
@@ -673,8 +1058,32 @@ impl egui_tiles::Behavior<ManagerPane> for ManagerBehavior
                             let (id, drawer) = drawer.pair();
 
                             ui.horizontal(|ui| {
-                                ui.image(egui::include_image!("../assets/ferris.png"));
+                                let images = self.images.read();
+
+                                // Display the drawer's costume if it has one.
+                                match drawer
+                                    .costume
+                                    .as_ref()
+                                    .and_then(|name| Some((name, images.images().get(name)?)))
+                                {
+                                    Some((name, image)) => {
+                                        ui.image(image_source(name, image));
+                                    },
+                                    None => {
+                                        ui.image(egui::include_image!("../assets/ferris.png"));
+                                    },
+                                }
+
                                 ui.label(id);
+
+                                if !drawer.enabled {
+                                    ui.label(RichText::from("Pen up").weak());
+                                }
+
+                                if !drawer.visible {
+                                    ui.label(RichText::from("Hidden").weak());
+                                }
+
                                 ui.menu_button("Info", |ui| {
                                     ui.label(format!("Angle: {}°", drawer.ang.to_degrees() - 90.));
                                     ui.label(format!(
@@ -682,11 +1091,38 @@ impl egui_tiles::Behavior<ManagerPane> for ManagerBehavior
                                         drawer.pos.x, drawer.pos.y
                                     ));
 
-                                    let color = drawer.color.to_linear();
+                                    // Display the color in the same format the scripts set it in.
+                                    let color = drawer.color.to_srgba();
 
                                     ui.label(format!(
-                                        "Color: Red: {} Green: {} Blue: {} Alpha: {}",
-                                        color.red, color.green, color.blue, color.alpha
+                                        "Color: Red: {} Green: {} Blue: {} Alpha: {} ({})",
+                                        color.red,
+                                        color.green,
+                                        color.blue,
+                                        color.alpha,
+                                        color.to_hex()
+                                    ));
+                                    ui.label(format!(
+                                        "Fill color: {}",
+                                        drawer
+                                            .fill_color
+                                            .map(|color| color.to_srgba().to_hex())
+                                            .unwrap_or_else(|| "same as the color".to_string())
+                                    ));
+
+                                    ui.label(format!(
+                                        "Pen: {} Visible: {}",
+                                        if drawer.enabled { "down" } else { "up" },
+                                        drawer.visible
+                                    ));
+                                    ui.label(format!("Pen size: {}", drawer.pen_size));
+                                    ui.label(format!(
+                                        "Costume: {}",
+                                        drawer.costume.as_deref().unwrap_or("Ferris")
+                                    ));
+                                    ui.label(format!(
+                                        "Line cap: {} Line join: {}",
+                                        drawer.line_cap, drawer.line_join
                                     ));
                                 });
                             });
@@ -929,6 +1365,80 @@ impl egui_tiles::Behavior<ManagerPane> for ManagerBehavior
                         });
                     });
             },
+            ManagerPane::ImageManager => {
+                ui.allocate_space(vec2(ui.available_width(), 2.));
+
+                // Check for the target family as file handling is not supported in WASM.
+                #[cfg(not(target_family = "wasm"))]
+                if ui.button("Add Image").clicked() {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .add_filter("Image", &["png", "jpg", "jpeg", "bmp", "gif"])
+                        .pick_file()
+                    {
+                        match read_project_image(&path) {
+                            Ok((name, image)) => {
+                                let mut images = self.images.write();
+
+                                if images.contains(&name) {
+                                    self.toasts.lock().add(
+                                        Toast::new()
+                                            .kind(egui_toast::ToastKind::Error)
+                                            .text(format!(
+                                                r#"An image with name "{name}" already exists."#
+                                            )),
+                                    );
+                                }
+                                else {
+                                    images.insert(name, image);
+                                }
+                            },
+                            Err(err) => {
+                                self.toasts.lock().add(
+                                    Toast::new()
+                                        .kind(egui_toast::ToastKind::Error)
+                                        .text(format!("Failed to add image: {err}")),
+                                );
+                            },
+                        }
+                    }
+                }
+
+                #[cfg(target_family = "wasm")]
+                ui.add_enabled_ui(false, |ui| {
+                    let _ = ui.button("Add Image").on_disabled_hover_text(
+                        RichText::from("File handling is not supported in WASM.")
+                            .color(Color32::RED),
+                    );
+                });
+
+                ui.separator();
+
+                ScrollArea::both()
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        let mut deleted_image = None;
+
+                        for (name, image) in self.images.read().images() {
+                            ui.horizontal(|ui| {
+                                ui.add(egui::Image::new(image_source(name, image)).max_height(32.));
+                                ui.label(name);
+
+                                if ui.button("Delete").clicked() {
+                                    deleted_image = Some(name.clone());
+                                }
+                            });
+                        }
+
+                        // Move the deleted image into the rubbish bin.
+                        if let Some(name) = deleted_image {
+                            if let Some(image) = self.images.write().remove(&name) {
+                                self.rubbish_bin
+                                    .lock()
+                                    .push(RubbishBinItem::Image(name, image));
+                            }
+                        }
+                    });
+            },
             ManagerPane::RubbishBin => {
                 ScrollArea::both()
                     .auto_shrink([false, false])
@@ -958,6 +1468,42 @@ impl egui_tiles::Behavior<ManagerPane> for ManagerBehavior
                                         };
                                     });
                                 },
+                                RubbishBinItem::Image(name, image) => {
+                                    ui.horizontal(|ui| {
+                                        ui.label(RichText::from("Image").weak());
+                                        ui.label(name.clone());
+                                        if ui.button("Restore").clicked() {
+                                            let mut images = self.images.write();
+
+                                            if images.contains(name) {
+                                                self.toasts.lock().add(
+                                                    Toast::new()
+                                                        .kind(egui_toast::ToastKind::Error)
+                                                        .text(format!(
+                                                            r#"An image with name "{name}" already exists."#
+                                                        )),
+                                                );
+                                            }
+                                            else {
+                                                images.insert(name.clone(), image.clone());
+
+                                                // Flag it to be deleted finally from this list.
+                                                should_be_retained = false;
+                                            }
+                                        };
+
+                                        if ui
+                                            .button(RichText::from("Delete").color(Color32::RED))
+                                            .clicked()
+                                        {
+                                            // Forget the image so that another image with this name can be displayed.
+                                            ui.ctx().forget_image(&image_uri(name, image));
+
+                                            // Flag it to be deleted finally.
+                                            should_be_retained = false;
+                                        };
+                                    });
+                                },
                                 RubbishBinItem::Demo(demo_instance) => {
                                     ui.horizontal(|ui| {
                                         ui.label(RichText::from("Demo").weak());
@@ -997,12 +1543,52 @@ impl egui_tiles::Behavior<ManagerPane> for ManagerBehavior
             ManagerPane::ScriptManager => format!("Scripts: {}", self.scripts.lock().len()),
             ManagerPane::EntityManager => format!("Entities: {}", self.drawers.len()),
             ManagerPane::DemoManager => format!("Demos: {}", self.demos.lock().len()),
+            ManagerPane::ImageManager => format!("Images: {}", self.images.read().images().len()),
             ManagerPane::RubbishBin => format!("Deleted: {}", self.rubbish_bin.lock().len()),
         }
         .into()
     }
 }
 
+/// Creates the uri egui caches the image under.
+fn image_uri(name: &str, image: &ProjectImage) -> String
+{
+    format!("bytes://project_images/{name}.{}", image.extension)
+}
+
+/// Creates an [`egui::ImageSource`] from a [`ProjectImage`], so that it can be displayed in the ui.
+fn image_source(name: &str, image: &ProjectImage) -> egui::ImageSource<'static>
+{
+    egui::ImageSource::Bytes {
+        uri: image_uri(name, image).into(),
+        bytes: image.bytes.clone().into(),
+    }
+}
+
+/// Reads an image from the disk, the name of the image is going to be the name of the file.
+#[cfg(not(target_family = "wasm"))]
+fn read_project_image(path: &Path) -> anyhow::Result<(String, ProjectImage)>
+{
+    let name = path
+        .file_stem()
+        .ok_or_else(|| anyhow::Error::msg("The file doesn't have a name."))?
+        .to_string_lossy()
+        .to_string();
+
+    let extension = path
+        .extension()
+        .ok_or_else(|| anyhow::Error::msg("The file doesn't have an extension."))?
+        .to_string_lossy()
+        .to_lowercase();
+
+    let image = ProjectImage::new(extension, fs::read(path)?.into());
+
+    // Check if the image can be displayed before adding it to the project.
+    image.decode()?;
+
+    Ok((name, image))
+}
+
 fn import_from_clipboard<T: for<'a> Deserialize<'a>>(
     ui: &mut egui::Ui,
     buffer: String,
@@ -1025,9 +1611,54 @@ pub fn main_ui(
     mut contexts: EguiContexts<'_, '_>,
     lua_runtime: ResMut<LuaRuntime>,
     drawers: Res<Drawers>,
+    #[cfg(not(target_family = "wasm"))] script_tasks: Res<ScriptTasks>,
 )
 {
     let ctx = contexts.ctx_mut();
+
+    // Continue executing the running scripts
+    #[cfg(not(target_family = "wasm"))]
+    for (origin, result) in script_tasks.poll(&lua_runtime) {
+        match origin {
+            TaskOrigin::CommandLine => {
+                if let Err(err) = result {
+                    ui_state
+                        .command_line_outputs
+                        .write()
+                        .push(ScriptLinePrompts::Error(err.to_string()));
+                }
+            },
+            TaskOrigin::Script(name) => {
+                let mut scripts = ui_state.scripts.lock();
+
+                // The script could have been stopped or deleted while it was running.
+                let Some(script_instance) = scripts
+                    .iter_mut()
+                    .find(|script_instance| script_instance.name == name && script_instance.is_running)
+                else {
+                    continue;
+                };
+
+                match result {
+                    Ok(()) => script_instance.collect_callbacks(),
+                    Err(err) => {
+                        ui_state
+                            .command_line_outputs
+                            .write()
+                            .push(ScriptLinePrompts::Error(err.to_string()));
+
+                        ui_state.toasts.lock().add(
+                            Toast::new()
+                                .kind(egui_toast::ToastKind::Error)
+                                .text(err.to_string()),
+                        );
+
+                        script_instance.mark_stopped();
+                    },
+                }
+            },
+        }
+    }
     
     // Call scripts with the `on_draw` callback
     #[cfg(target_family = "wasm")]
@@ -1052,14 +1683,17 @@ pub fn main_ui(
                 &ui_state,
                 &lua_runtime,
                 CallbackType::OnDraw,
-                vec![pos.x, pos.y].iter().enumerate().map(|(idx, pos)| {(idx, *pos as i32)})
+                [pos.x, pos.y].iter().enumerate().map(|(idx, pos)| {(idx, *pos as i32)})
             );
         }
     }
 
+    // The keys typed into a text field, ie. the Command Panel, are not sent to the scripts.
+    let is_typing = ctx.wants_keyboard_input();
+
     // Call scripts with the `on_input` callback
     ctx.input(|reader| {
-        if reader.focused {
+        if reader.focused && !is_typing {
             let keys_down = reader.keys_down.clone();
             let callback_type = CallbackType::OnInput;
 
@@ -1086,6 +1720,45 @@ pub fn main_ui(
         }
     });
 
+    // Call scripts with the `on_key_pressed` and `on_key_released` callbacks
+    // The keys released while typing are still sent, as the scripts could have seen them pressed before the text field was focused.
+    #[cfg(not(target_family = "wasm"))]
+    {
+        let key_events: Vec<(CallbackType, String)> = ctx.input(|reader| {
+            reader
+                .events
+                .iter()
+                .filter_map(|event| {
+                    match event {
+                        egui::Event::Key {
+                            key,
+                            pressed: true,
+                            repeat: false,
+                            ..
+                        } if !is_typing => Some((CallbackType::OnKeyPressed, key.name().to_string())),
+                        egui::Event::Key {
+                            key,
+                            pressed: false,
+                            ..
+                        } => Some((CallbackType::OnKeyReleased, key.name().to_string())),
+                        _ => None,
+                    }
+                })
+                .collect()
+        });
+
+        for (callback_type, key) in key_events {
+            invoke_callback_with_args(
+                &ui_state,
+                &lua_runtime,
+                &script_tasks,
+                &drawers,
+                callback_type,
+                key,
+            );
+        }
+    }
+
     egui_extras::install_image_loaders(ctx);
 
     ui_state.toasts.lock().show(ctx);
@@ -1102,6 +1775,43 @@ pub fn main_ui(
 
     ui_state.documentation_window = documentation_window_is_open;
 
+    // The project asks to be trusted, but only the user can decide whether to trust it.
+    #[cfg(not(target_family = "wasm"))]
+    if ui_state.trust_prompt {
+        let mut answer = None;
+
+        egui::Window::new("Trust project")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("This project asks to be trusted, which allows its scripts to read and write files, and to require external modules. Only trust the projects you know.");
+
+                ui.horizontal(|ui| {
+                    if ui.button("Trust").clicked() {
+                        answer = Some(true);
+                    }
+
+                    if ui.button("Don't trust").clicked() {
+                        answer = Some(false);
+                    }
+                });
+            });
+
+        if let Some(trusted) = answer {
+            ui_state.trust_prompt = false;
+            ui_state.trusted = trusted;
+            ui_state.requests_trust = trusted;
+
+            if let Err(err) = lua_runtime.set_trusted(trusted) {
+                ui_state.toasts.lock().add(
+                    Toast::new()
+                        .kind(egui_toast::ToastKind::Error)
+                        .text(err.to_string()),
+                );
+            }
+        }
+    }
+
     bevy_egui::egui::TopBottomPanel::top("top_panel")
         .resizable(true)
         .show(ctx, |ui| {
@@ -1141,10 +1851,56 @@ pub fn main_ui(
                                         miniz_oxide::inflate::decompress_to_vec(&read_bytes)
                                             .unwrap();
 
-                                    let data: UiState =
+                                    let mut data: UiState =
                                         rmp_serde::from_slice(&decompressed_data).unwrap();
 
+                                    // The lua runtime holds a handle to the images, so the loaded images are moved into the existing list.
+                                    let loaded_images = std::mem::take(&mut *data.images.write());
+                                    ui_state.images.write().replace(loaded_images);
+                                    data.images = ui_state.images.clone();
+
+                                    // The palette is shared with the lua runtime the same way.
+                                    let loaded_palette = std::mem::take(&mut *data.palette.write());
+                                    *ui_state.palette.write() = loaded_palette;
+                                    data.palette = ui_state.palette.clone();
+
+                                    let loaded_animation_speed = *data.animation_speed.read();
+                                    *ui_state.animation_speed.write() = loaded_animation_speed;
+                                    data.animation_speed = ui_state.animation_speed.clone();
+
+                                    let loaded_execution_limits = *data.execution_limits.read();
+                                    *ui_state.execution_limits.write() = loaded_execution_limits;
+                                    data.execution_limits = ui_state.execution_limits.clone();
+
+                                    limit_memory(&lua_runtime, loaded_execution_limits.max_memory_megabytes);
+
+                                    // The images of the new project may have the same names as the old ones.
+                                    ui.ctx().forget_all_images();
+
+                                    // The code of the old project would keep running, and its callbacks and timers would keep being called.
+                                    for script_instance in ui_state
+                                        .scripts
+                                        .lock()
+                                        .iter_mut()
+                                        .filter(|script_instance| script_instance.is_running)
+                                    {
+                                        script_instance.stop(&script_tasks, &drawers, false);
+                                    }
+
+                                    script_tasks.cancel(&TaskOrigin::CommandLine);
+
                                     *ui_state = data;
+
+                                    // The new project isn't trusted until the user says so.
+                                    if let Err(err) = lua_runtime.set_trusted(false) {
+                                        ui_state.toasts.lock().add(
+                                            Toast::new()
+                                                .kind(egui_toast::ToastKind::Error)
+                                                .text(err.to_string()),
+                                        );
+                                    }
+
+                                    ui_state.trust_prompt = ui_state.requests_trust;
                                 },
                                 Err(_err) => {
                                     ui_state.toasts.lock().add(
@@ -1179,6 +1935,85 @@ pub fn main_ui(
                     ui_state.documentation_window = !ui_state.documentation_window;
                 }
 
+                #[cfg(not(target_family = "wasm"))]
+                ui.menu_button("Settings", |ui| {
+                    let mut execution_limits = ui_state.execution_limits.write();
+                    let max_memory_megabytes = execution_limits.max_memory_megabytes;
+
+                    ui.label("A script is stopped if it runs longer than this without pausing, 0 disables the limit.");
+
+                    ui.add(
+                        DragValue::new(&mut execution_limits.max_seconds)
+                            .range(0.0..=600.)
+                            .speed(0.1)
+                            .suffix(" s"),
+                    );
+
+                    ui.label("A script is stopped if it executes more instructions than this without pausing, 0 disables the limit.");
+
+                    ui.add(
+                        DragValue::new(&mut execution_limits.max_instructions)
+                            .speed(1_000_000),
+                    );
+
+                    ui.label("The lua runtime can't use more memory than this, a script is stopped if it runs out of memory. 0 disables the limit.");
+
+                    ui.add(
+                        DragValue::new(&mut execution_limits.max_memory_megabytes)
+                            .speed(8)
+                            .suffix(" MB"),
+                    );
+
+                    ui.label("The most drawers which can exist at once, 0 disables the limit.");
+
+                    ui.add(DragValue::new(&mut execution_limits.max_drawers).speed(10));
+
+                    ui.label("A script is stopped if the drawings contain more points than this, every text and stamp counts as one point. 0 disables the limit.");
+
+                    ui.add(DragValue::new(&mut execution_limits.max_points).speed(10_000));
+
+                    if ui.button("Reset to defaults").clicked() {
+                        *execution_limits = ExecutionLimits::default();
+                    }
+
+                    if execution_limits.max_memory_megabytes != max_memory_megabytes {
+                        limit_memory(&lua_runtime, execution_limits.max_memory_megabytes);
+                    }
+
+                    drop(execution_limits);
+
+                    ui.separator();
+
+                    let trusted_checkbox = ui
+                        .checkbox(&mut ui_state.trusted, "Trusted project")
+                        .on_hover_text("Allows the scripts to read and write files, and to require external modules. Only trust the projects you know.");
+
+                    if trusted_checkbox.changed() {
+                        ui_state.requests_trust = ui_state.trusted;
+
+                        if let Err(err) = lua_runtime.set_trusted(ui_state.trusted) {
+                            ui_state.toasts.lock().add(
+                                Toast::new()
+                                    .kind(egui_toast::ToastKind::Error)
+                                    .text(err.to_string()),
+                            );
+                        }
+                    }
+                });
+
+                // The drawers are only animated when the scripts are run as tasks, which is not supported in WASM.
+                #[cfg(not(target_family = "wasm"))]
+                ui.add(
+                    Slider::new(&mut ui_state.animation_speed.write().0, 0..=AnimationSpeed::MAX)
+                        .text("Drawing speed")
+                        .custom_formatter(|level, _| {
+                            match AnimationSpeed(level as u8).units_per_second() {
+                                Some(units_per_second) => format!("{units_per_second:.0} units/s"),
+                                None => "Instant".to_string(),
+                            }
+                        }),
+                );
+
                 #[cfg(target_family = "wasm")]
                 ui.hyperlink_to(
                     "Get the full Desktop Version!",
@@ -1202,6 +2037,9 @@ pub fn main_ui(
                 let demo_buffer = ui_state.demo_buffer.clone();
                 let scripts = ui_state.scripts.clone();
                 let demo_text_buffer = ui_state.demo_rename_text_buffer.clone();
+                let images = ui_state.images.clone();
+                #[cfg(not(target_family = "wasm"))]
+                let palette = ui_state.palette.clone();
 
                 ui_state.item_manager.ui(
                     &mut ManagerBehavior {
@@ -1215,6 +2053,11 @@ pub fn main_ui(
                         demo_buffer,
                         scripts,
                         import_from_clipboard_buffer: demo_text_buffer,
+                        images,
+                        #[cfg(not(target_family = "wasm"))]
+                        script_tasks: script_tasks.clone(),
+                        #[cfg(not(target_family = "wasm"))]
+                        palette,
                     },
                     ui,
                 );
@@ -1262,6 +2105,17 @@ pub fn main_ui(
 
                     ui.horizontal_centered(|ui| {
                         ui.group(|ui| {
+                            // The commands can wait and walk the drawers, so they can be stopped the same way as the scripts.
+                            #[cfg(not(target_family = "wasm"))]
+                            if script_tasks.is_running(&TaskOrigin::CommandLine)
+                                && ui
+                                    .button("Stop")
+                                    .on_hover_text("Stop the commands which are still running.")
+                                    .clicked()
+                            {
+                                script_tasks.cancel(&TaskOrigin::CommandLine);
+                            }
+
                             // Indicate the terminal input.
                             ui.label(RichText::from("$>").color(Color32::WHITE));
 
@@ -1343,7 +2197,17 @@ pub fn main_ui(
                                                 ),
                                             );
 
-                                            // Check if it has the "wasm" target family, as the lua runtime is not supported in wasm
+                                            // The command is run as a task, so that the drawers can be animated.
+                                            // Its error is displayed when the task has finished.
+                                            #[cfg(not(target_family = "wasm"))]
+                                            script_tasks.spawn(
+                                                &lua_runtime,
+                                                &command_line_buffer,
+                                                TaskOrigin::CommandLine,
+                                                None,
+                                            );
+
+                                            #[cfg(target_family = "wasm")]
                                             match lua_runtime.execute_code(&command_line_buffer) {
                                                 Ok(_output) => (),
                                                 Err(_err) => {
@@ -1549,7 +2413,9 @@ fn invoke_callback_from_scripts<K, V>(
         }
 
         // If the data is a Some that means that we want to invoke the callback with an argument passed in.
-        if let Some(function) = script.callbacks.get(&callback_type) {
+        if let Some(function) = script.callback(callback_type) {
+            restart_execution_budget(lua_runtime);
+
             if let Err(err) = function.call::<()>(lua_runtime.create_table_from(argument.clone())) {
                 // Add the error into the toasts if it returned an error
                 ui_state.toasts.lock().add(
@@ -1558,12 +2424,262 @@ fn invoke_callback_from_scripts<K, V>(
                         .text(err.to_string()),
                     );
                     
-                    script.is_running = false;
+                    script.mark_stopped();
                 };
         };
     }
 }
 
+/// Calls the callback of every running script with the arguments.
+/// The scripts whose callback has returned an error are stopped.
+#[cfg(not(target_family = "wasm"))]
+fn invoke_callback_with_args(
+    ui_state: &UiState,
+    lua_runtime: &LuaRuntime,
+    script_tasks: &ScriptTasks,
+    drawers: &Drawers,
+    callback_type: CallbackType,
+    args: impl IntoLuaMulti + Clone,
+)
+{
+    for script in ui_state.scripts.lock().iter_mut() {
+        if let Err(err) = script.call_callback(lua_runtime, callback_type, args.clone()) {
+            ui_state.toasts.lock().add(
+                Toast::new()
+                    .kind(egui_toast::ToastKind::Error)
+                    .text(err.to_string()),
+            );
+
+            script.stop(script_tasks, drawers, false);
+        }
+    }
+}
+
+/// How far the pointer can move between pressing and releasing a mouse button for it to count as a click, in logical pixels.
+#[cfg(not(target_family = "wasm"))]
+const CLICK_DISTANCE: f32 = 4.;
+
+/// The state of the mouse over the canvas, which is kept between the frames by [`canvas_pointer_callbacks`].
+#[cfg(not(target_family = "wasm"))]
+#[derive(Default)]
+pub struct CanvasPointer
+{
+    /// The mouse buttons pressed over the canvas, with the position of the cursor where they were pressed.
+    pressed: HashMap<MouseButton, Vec2>,
+    /// The last known position of the pointer in world coordinates, this is kept while the pointer is outside of the window.
+    last_position: Option<Vec2>,
+}
+
+/// Returns the name of the mouse button, which is passed to the scripts' callbacks.
+#[cfg(not(target_family = "wasm"))]
+fn mouse_button_name(button: MouseButton) -> String
+{
+    match button {
+        MouseButton::Left => "left".to_string(),
+        MouseButton::Right => "right".to_string(),
+        MouseButton::Middle => "middle".to_string(),
+        MouseButton::Back => "back".to_string(),
+        MouseButton::Forward => "forward".to_string(),
+        MouseButton::Other(idx) => format!("button{idx}"),
+    }
+}
+
+/// Calls the `on_mouse_down`, `on_mouse_up`, `on_click` and `on_drag` callbacks of the scripts.
+/// The positions are in world coordinates, the same coordinates the drawers use.
+/// The buttons are only handled if they were pressed over the canvas, not over one of the panels.
+#[cfg(not(target_family = "wasm"))]
+#[allow(clippy::too_many_arguments)]
+pub fn canvas_pointer_callbacks(
+    ui_state: Res<UiState>,
+    mut contexts: EguiContexts<'_, '_>,
+    lua_runtime: Res<LuaRuntime>,
+    script_tasks: Res<ScriptTasks>,
+    drawers: Res<Drawers>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    windows: Query<&bevy::window::Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    mut pointer: bevy::prelude::Local<CanvasPointer>,
+)
+{
+    let ctx = contexts.ctx_mut();
+
+    let is_over_canvas = !ctx.is_pointer_over_area() && !ctx.is_using_pointer();
+
+    let cursor = windows
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position());
+
+    let position = cursor.and_then(|cursor| {
+        let (camera, camera_transform) = cameras.get_single().ok()?;
+
+        camera.viewport_to_world_2d(camera_transform, cursor).ok()
+    });
+
+    let invoke = |callback_type: CallbackType, args: (f32, f32, String)| {
+        invoke_callback_with_args(
+            &ui_state,
+            &lua_runtime,
+            &script_tasks,
+            &drawers,
+            callback_type,
+            args,
+        );
+    };
+
+    if let (Some(cursor), Some(position)) = (cursor, position) {
+        for button in mouse_buttons.get_just_pressed() {
+            if is_over_canvas {
+                pointer.pressed.insert(*button, cursor);
+
+                invoke(
+                    CallbackType::OnMouseDown,
+                    (position.x, position.y, mouse_button_name(*button)),
+                );
+            }
+        }
+
+        if let Some(last_position) = pointer.last_position {
+            let delta = position - last_position;
+
+            if delta != Vec2::ZERO {
+                for button in pointer.pressed.keys() {
+                    invoke_callback_with_args(
+                        &ui_state,
+                        &lua_runtime,
+                        &script_tasks,
+                        &drawers,
+                        CallbackType::OnDrag,
+                        (
+                            position.x,
+                            position.y,
+                            delta.x,
+                            delta.y,
+                            mouse_button_name(*button),
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    for button in mouse_buttons.get_just_released() {
+        // The buttons pressed over the panels are ignored.
+        let Some(pressed_at) = pointer.pressed.remove(button)
+        else {
+            continue;
+        };
+
+        // The button could have been released outside of the window, then the pointer's last known position is used.
+        let Some(release_position) = position.or(pointer.last_position)
+        else {
+            continue;
+        };
+
+        let name = mouse_button_name(*button);
+
+        invoke(
+            CallbackType::OnMouseUp,
+            (release_position.x, release_position.y, name.clone()),
+        );
+
+        // Only the buttons released over the canvas are clicks.
+        if is_over_canvas && cursor.is_some_and(|cursor| cursor.distance(pressed_at) <= CLICK_DISTANCE) {
+            invoke(CallbackType::OnClick, (release_position.x, release_position.y, name));
+        }
+    }
+
+    if position.is_some() {
+        pointer.last_position = position;
+    }
+}
+
+/// Calls the `on_tick` callbacks of the scripts every frame, with the time elapsed since the previous frame in seconds.
+#[cfg(not(target_family = "wasm"))]
+pub fn tick_callbacks(
+    ui_state: Res<UiState>,
+    lua_runtime: Res<LuaRuntime>,
+    script_tasks: Res<ScriptTasks>,
+    drawers: Res<Drawers>,
+    time: Res<Time>,
+)
+{
+    invoke_callback_with_args(
+        &ui_state,
+        &lua_runtime,
+        &script_tasks,
+        &drawers,
+        CallbackType::OnTick,
+        time.delta_secs(),
+    );
+}
+
+/// Calls the functions the scripts have scheduled with `after` and `every` when they are due.
+/// A script without callbacks is stopped once all of its timers have finished.
+#[cfg(not(target_family = "wasm"))]
+pub fn timer_callbacks(
+    ui_state: Res<UiState>,
+    lua_runtime: Res<LuaRuntime>,
+    script_tasks: Res<ScriptTasks>,
+    drawers: Res<Drawers>,
+    time: Res<Time>,
+)
+{
+    for script in ui_state.scripts.lock().iter_mut() {
+        if !script.is_running {
+            continue;
+        }
+
+        script.timers.advance(time.delta_secs());
+
+        while let Some(function) = script.timers.take_due() {
+            restart_execution_budget(&lua_runtime);
+
+            if let Err(err) = function.call::<()>(()) {
+                ui_state.toasts.lock().add(
+                    Toast::new()
+                        .kind(egui_toast::ToastKind::Error)
+                        .text(err.to_string()),
+                );
+
+                script.stop(&script_tasks, &drawers, false);
+
+                break;
+            }
+        }
+
+        // The script is still running while its code is being executed, even if it has nothing scheduled yet.
+        if script.is_running
+            && script.callbacks.is_empty()
+            && script.timers.is_empty()
+            && !script_tasks.is_running(&TaskOrigin::Script(script.name.clone()))
+        {
+            script.mark_stopped();
+        }
+    }
+}
+
+/// Calls the `on_fixed_tick` callbacks of the scripts at the fixed timestep of the app, with the length of the timestep in seconds.
+/// This system must run in the `FixedUpdate` schedule, where the time is the fixed time.
+#[cfg(not(target_family = "wasm"))]
+pub fn fixed_tick_callbacks(
+    ui_state: Res<UiState>,
+    lua_runtime: Res<LuaRuntime>,
+    script_tasks: Res<ScriptTasks>,
+    drawers: Res<Drawers>,
+    time: Res<Time>,
+)
+{
+    invoke_callback_with_args(
+        &ui_state,
+        &lua_runtime,
+        &script_tasks,
+        &drawers,
+        CallbackType::OnFixedTick,
+        time.delta_secs(),
+    );
+}
+
 #[cfg(target_family = "wasm")]
 fn invoke_callback_from_scripts_wasm(
     ui_state: &ResMut<'_, UiState>,
@@ -1588,7 +2704,7 @@ fn invoke_callback_from_scripts_wasm(
                             .text(err.to_string()),
                         );
                         
-                    script.is_running = false;
+                    script.mark_stopped();
                 },
             }
         }